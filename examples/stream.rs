@@ -1,4 +1,4 @@
-use clipboard_stream::{Body, ClipboardEventListener};
+use clipboard_watcher::{Body, ClipboardEventListener};
 use futures::StreamExt;
 use log::LevelFilter;
 
@@ -14,17 +14,17 @@ async fn main() {
 
   while let Some(result) = stream.next().await {
     match result {
-      Ok(content) => {
-        match content.as_ref() {
-          Body::PlainText(v) => println!("Received string:\n{v}"),
-          Body::Image(image) => {
+      Ok(event) => {
+        match event.body() {
+          Some(Body::PlainText(v)) => println!("Received string:\n{v}"),
+          Some(Body::Image(image)) => {
             println!("Received image");
             if let Some(path) = &image.path {
               println!("Image Path: {path:#?}");
             }
           }
-          Body::FileList(files) => println!("Received files: {files:#?}"),
-          Body::Html(html) => println!("Received html: \n{html}"),
+          Some(Body::FileList(files)) => println!("Received files: {files:#?}"),
+          Some(Body::Html(html)) => println!("Received html: \n{}", html.fragment),
           _ => {}
         };
       }
@@ -0,0 +1,71 @@
+use std::fmt;
+
+use crate::sink::ClipboardSink;
+
+/// A source of clipboard changes, which can be used in place of the system clipboard with
+/// [`ClipboardEventListenerBuilder::backend`](crate::ClipboardEventListenerBuilder::backend).
+///
+/// The items sent to the [`ClipboardSink`] go through the same filters as the ones coming from the system clipboard,
+/// and are delivered to every [`ClipboardStream`](crate::ClipboardStream) of the listener.
+///
+/// # Example
+/// ```
+/// use clipboard_watcher::{Body, ClipboardBackend, ClipboardKind, ClipboardSink};
+///
+/// struct Greeter;
+///
+/// impl ClipboardBackend for Greeter {
+///     fn observe(&mut self, sink: ClipboardSink) {
+///         while !sink.is_stopped() {
+///             sink.send(ClipboardKind::Clipboard, Body::PlainText("Hello!".to_string()));
+///             std::thread::sleep(sink.interval());
+///         }
+///     }
+/// }
+/// ```
+pub trait ClipboardBackend: Send + 'static {
+  /// Monitors the clipboard and sends its changes to the sink.
+  ///
+  /// This method runs in a dedicated thread, and it should return as soon as [`ClipboardSink::is_stopped`] returns `true`,
+  /// since dropping the [`ClipboardEventListener`](crate::ClipboardEventListener) blocks until it does.
+  fn observe(&mut self, sink: ClipboardSink);
+}
+
+/// The mechanism used by a [`ClipboardEventListener`](crate::ClipboardEventListener) to monitor the clipboard.
+///
+/// On Linux, the best backend available in the current session is picked when the listener is spawned,
+/// falling back to the next one in this order: [`WaylandDataControl`](Backend::WaylandDataControl),
+/// [`X11XFixes`](Backend::X11XFixes), [`X11Polling`](Backend::X11Polling).
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Backend {
+  /// The clipboard format listener of the Windows API.
+  Windows,
+  /// The change count of the macOS pasteboard, checked at every polling interval.
+  MacOs,
+  /// The Wayland data-control protocol (either `ext-data-control-v1` or `wlr-data-control-unstable-v1`).
+  WaylandDataControl,
+  /// The X11 XFixes extension, which notifies every change of the selections.
+  X11XFixes,
+  /// The owners of the X11 selections, checked at every polling interval.
+  ///
+  /// This is only used when the XFixes extension is not available.
+  X11Polling,
+  /// A custom [`ClipboardBackend`].
+  Custom,
+}
+
+impl fmt::Display for Backend {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let name = match self {
+      Backend::Windows => "Windows",
+      Backend::MacOs => "macOS",
+      Backend::WaylandDataControl => "Wayland data-control",
+      Backend::X11XFixes => "X11 XFixes",
+      Backend::X11Polling => "X11 polling",
+      Backend::Custom => "custom",
+    };
+
+    f.write_str(name)
+  }
+}
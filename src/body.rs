@@ -2,43 +2,186 @@ use std::{
   collections::HashMap,
   path::PathBuf,
   sync::{Arc, Mutex},
+  time::SystemTime,
 };
 
+use bytes::Bytes;
 use futures::channel::mpsc::Sender;
+use image::ColorType;
 use log::error;
+use url::Url;
 
 use crate::{error::ClipboardResult, stream::StreamId};
 
 /// The content extracted from the clipboard.
 ///
-/// To avoid extracting all types of content each time, only one of them is chosen, in the following order of priority:
+/// To avoid extracting all types of content each time, only one of them is chosen, by default in the following order of priority:
 ///
 /// - Custom formats (in the order they are given, if present)
 /// - Image (see [`ClipboardImage`] for more info)
 /// - File list
+/// - URI list
 /// - HTML
+/// - Rich Text Format
 /// - Plain text
 ///
 /// When a clipboard item can fit more than one of these formats, only the one with the highest priority will be chosen.
+/// The order can be changed with [`priority`](crate::ClipboardEventListenerBuilder::priority).
 ///
 /// When selecting a single image as a file, the item will be processed as an Image (with a defined file path), falling back to a single-item file list in case the processing of the image goes wrong.
+/// This only happens as long as images have a higher priority than file lists.
+///
+/// Binary payloads (images and custom formats) are held in [`Bytes`], so cloning a body shares them instead of copying them.
+///
+/// When serialized, the content is tagged with the name of its variant, since some of them (such as [`Rtf`](Body::Rtf)
+/// and [`PlainText`](Body::PlainText)) could not be told apart otherwise.
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
-#[cfg_attr(feature = "serde", serde(untagged))]
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Body {
-  Html(String),
+  Html(HtmlContent),
+  /// A document in the Rich Text Format, as its source.
+  Rtf(String),
   PlainText(String),
   Image(ClipboardImage),
-  FileList(Vec<PathBuf>),
-  Custom { name: Arc<str>, data: Vec<u8> },
+  FileList(Vec<FileEntry>),
+  /// A list of URIs that do not point to local files, such as web links or remote locations (`sftp://`, `smb://`).
+  ///
+  /// Local files are listed in a [`FileList`](Body::FileList) instead.
+  UriList(Vec<Url>),
+  Custom {
+    name: Arc<str>,
+    data: Bytes,
+  },
+}
+
+/// A kind of [`Body`], used to choose which content is extracted and in which order.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FormatKind {
+  /// The custom formats given with [`with_custom_formats`](crate::ClipboardEventListenerBuilder::with_custom_formats).
+  Custom,
+  Image,
+  FileList,
+  UriList,
+  Html,
+  Rtf,
+  PlainText,
+}
+
+impl Body {
+  /// Returns the [`FormatKind`] of the content.
+  pub fn format_kind(&self) -> FormatKind {
+    match self {
+      Body::Html(_) => FormatKind::Html,
+      Body::Rtf(_) => FormatKind::Rtf,
+      Body::PlainText(_) => FormatKind::PlainText,
+      Body::Image(_) => FormatKind::Image,
+      Body::FileList(_) => FormatKind::FileList,
+      Body::UriList(_) => FormatKind::UriList,
+      Body::Custom { .. } => FormatKind::Custom,
+    }
+  }
+}
+
+impl FormatKind {
+  /// The default order of priority, as described in [`Body`].
+  pub const DEFAULT_PRIORITY: [FormatKind; 7] = [
+    FormatKind::Custom,
+    FormatKind::Image,
+    FormatKind::FileList,
+    FormatKind::UriList,
+    FormatKind::Html,
+    FormatKind::Rtf,
+    FormatKind::PlainText,
+  ];
+}
+
+/// HTML content from the clipboard.
+///
+/// Every platform describes it in its own way (such as the `CF_HTML` header on Windows), so it is parsed into the selected fragment
+/// and the document that contains it.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct HtmlContent {
+  /// The markup of the selection.
+  pub fragment: String,
+  /// The full document that contains the [`fragment`](Self::fragment), if the clipboard offers more than the fragment itself.
+  pub document: Option<String>,
+  /// The URL of the page that the content was copied from, if the clipboard provides it.
+  pub source_url: Option<Url>,
+}
+
+impl HtmlContent {
+  /// Returns the full document if available, or else the fragment.
+  pub fn markup(&self) -> &str {
+    self.document.as_deref().unwrap_or(&self.fragment)
+  }
+}
+
+/// A file from a [`FileList`](Body::FileList).
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FileEntry {
+  pub path: PathBuf,
+  /// What was found by inspecting the file, when [`file_metadata`](crate::ClipboardEventListenerBuilder::file_metadata)
+  /// is enabled and the entry is within its limit.
+  pub metadata: Option<FileMetadata>,
 }
 
-/// An image from the clipboard, normalized to the PNG format.
+impl FileEntry {
+  /// Creates an entry without metadata, such as one to be written to the clipboard.
+  pub fn new(path: impl Into<PathBuf>) -> Self {
+    FileEntry {
+      path: path.into(),
+      metadata: None,
+    }
+  }
+}
+
+impl From<PathBuf> for FileEntry {
+  fn from(path: PathBuf) -> Self {
+    FileEntry::new(path)
+  }
+}
+
+/// Information about a [`FileEntry`], gathered when the change was detected.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FileMetadata {
+  /// Whether the file exists. If it does not, or if it cannot be inspected (such as without permission), the other fields are empty.
+  pub exists: bool,
+  pub is_dir: bool,
+  /// The size of the file in bytes. Directories and special files (such as pipes or devices) have none.
+  pub size: Option<u64>,
+  /// When the file was last modified, if the platform provides it.
+  pub modified: Option<SystemTime>,
+  /// The MIME type of the file, as sniffed from its first bytes (such as `image/png` or `text/plain`).
+  ///
+  /// Directories, special files and unreadable files have none, and files of an unknown binary format are `application/octet-stream`.
+  pub mime_type: Option<String>,
+}
+
+/// An image from the clipboard.
+///
+/// By default, it is converted to the PNG format. The encoding can be chosen with [`image_encoding`](crate::ClipboardEventListenerBuilder::image_encoding),
+/// or the original bytes can be kept with [`keep_image_encoding`](crate::ClipboardEventListenerBuilder::keep_image_encoding).
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ClipboardImage {
-  /// The bytes that compose the image, encoded in the PNG format.
-  pub bytes: Vec<u8>,
+  /// The bytes that compose the image, encoded as described by [`mime_type`](Self::mime_type).
+  pub bytes: Bytes,
+  /// The format in which the image is encoded.
+  pub mime_type: MimeType,
+  /// The width of the image, in pixels.
+  pub width: u32,
+  /// The height of the image, in pixels.
+  pub height: u32,
+  /// The color type of the image, as it is encoded.
+  pub color_type: ColorType,
+  /// A small preview of the image, encoded in the PNG format.
+  ///
+  /// It is only created when [`image_thumbnails`](crate::ClipboardEventListenerBuilder::image_thumbnails) are enabled.
+  pub thumbnail: Option<Bytes>,
   /// The path to the image's file (if one can be detected).
   pub path: Option<PathBuf>,
 }
@@ -50,6 +193,160 @@ impl ClipboardImage {
   }
 }
 
+/// The format of a [`ClipboardImage`].
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MimeType {
+  Png,
+  Jpeg,
+  Gif,
+  WebP,
+  Tiff,
+  Bmp,
+}
+
+impl MimeType {
+  /// Returns the MIME type as a string, such as `image/png`.
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      MimeType::Png => "image/png",
+      MimeType::Jpeg => "image/jpeg",
+      MimeType::Gif => "image/gif",
+      MimeType::WebP => "image/webp",
+      MimeType::Tiff => "image/tiff",
+      MimeType::Bmp => "image/bmp",
+    }
+  }
+
+  /// Parses a MIME type such as `image/png`, if it is one of the supported formats.
+  pub fn from_mime(mime: &str) -> Option<Self> {
+    match mime {
+      "image/png" => Some(MimeType::Png),
+      "image/jpeg" | "image/jpg" => Some(MimeType::Jpeg),
+      "image/gif" => Some(MimeType::Gif),
+      "image/webp" => Some(MimeType::WebP),
+      "image/tiff" => Some(MimeType::Tiff),
+      "image/bmp" => Some(MimeType::Bmp),
+      _ => None,
+    }
+  }
+}
+
+impl std::fmt::Display for MimeType {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+/// The encoding used when a [`ClipboardImage`] has to be converted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ImageEncoding {
+  /// The PNG format, which is lossless.
+  #[default]
+  Png,
+  /// The JPEG format, with a quality between 1 and 100.
+  ///
+  /// It has no transparency, so transparent pixels lose their alpha channel.
+  Jpeg { quality: u8 },
+  /// The lossless WebP format.
+  WebP,
+}
+
+impl ImageEncoding {
+  /// Returns the [`MimeType`] of the images produced with this encoding.
+  pub fn mime_type(&self) -> MimeType {
+    match self {
+      ImageEncoding::Png => MimeType::Png,
+      ImageEncoding::Jpeg { .. } => MimeType::Jpeg,
+      ImageEncoding::WebP => MimeType::WebP,
+    }
+  }
+}
+
+/// Every representation of a clipboard item, in the same order of priority used for [`Body`].
+///
+/// It is only produced when [`snapshots`](crate::ClipboardEventListenerBuilder::snapshots) are enabled.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ClipboardSnapshot {
+  representations: Vec<Body>,
+}
+
+impl ClipboardSnapshot {
+  /// Creates a snapshot from a non-empty list of representations.
+  pub(crate) fn new(representations: Vec<Body>) -> Self {
+    debug_assert!(!representations.is_empty());
+
+    ClipboardSnapshot { representations }
+  }
+
+  /// Returns the representation with the highest priority, which is the one that would be chosen without snapshots.
+  pub fn primary(&self) -> &Body {
+    &self.representations[0]
+  }
+
+  /// Returns all of the representations, in order of priority.
+  pub fn representations(&self) -> &[Body] {
+    &self.representations
+  }
+
+  /// Returns the HTML representation, if present.
+  pub fn html(&self) -> Option<&HtmlContent> {
+    self.representations.iter().find_map(|body| match body {
+      Body::Html(html) => Some(html),
+      _ => None,
+    })
+  }
+
+  /// Returns the Rich Text Format representation, if present.
+  pub fn rtf(&self) -> Option<&str> {
+    self.representations.iter().find_map(|body| match body {
+      Body::Rtf(rtf) => Some(rtf.as_str()),
+      _ => None,
+    })
+  }
+
+  /// Returns the plain text representation, if present.
+  pub fn plain_text(&self) -> Option<&str> {
+    self.representations.iter().find_map(|body| match body {
+      Body::PlainText(text) => Some(text.as_str()),
+      _ => None,
+    })
+  }
+
+  /// Returns the image representation, if present.
+  pub fn image(&self) -> Option<&ClipboardImage> {
+    self.representations.iter().find_map(|body| match body {
+      Body::Image(image) => Some(image),
+      _ => None,
+    })
+  }
+
+  /// Returns the file list representation, if present.
+  pub fn file_list(&self) -> Option<&[FileEntry]> {
+    self.representations.iter().find_map(|body| match body {
+      Body::FileList(files) => Some(files.as_slice()),
+      _ => None,
+    })
+  }
+
+  /// Returns the URI list representation, if present.
+  pub fn uri_list(&self) -> Option<&[Url]> {
+    self.representations.iter().find_map(|body| match body {
+      Body::UriList(uris) => Some(uris.as_slice()),
+      _ => None,
+    })
+  }
+
+  /// Returns the data for the given custom format, if present.
+  pub fn custom(&self, format: &str) -> Option<&Bytes> {
+    self.representations.iter().find_map(|body| match body {
+      Body::Custom { name, data } if name.as_ref() == format => Some(data),
+      _ => None,
+    })
+  }
+}
+
 #[derive(Debug)]
 pub(crate) struct BodySenders {
   senders: Mutex<HashMap<StreamId, Sender<ClipboardResult>>>,
@@ -101,3 +398,62 @@ impl BodySendersDropHandle {
     self.0.unregister(id);
   }
 }
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+  use super::*;
+
+  fn round_trip(body: Body) {
+    let json = serde_json::to_string(&body).unwrap();
+
+    assert_eq!(serde_json::from_str::<Body>(&json).unwrap(), body, "{json}");
+  }
+
+  #[test]
+  fn text_bodies_round_trip() {
+    round_trip(Body::PlainText("{\\rtf1 not a document}".to_string()));
+    round_trip(Body::Rtf("{\\rtf1 Hello}".to_string()));
+    round_trip(Body::Html(HtmlContent {
+      fragment: "<b>Hello</b>".to_string(),
+      document: None,
+      source_url: Some(Url::parse("https://example.com").unwrap()),
+    }));
+  }
+
+  #[test]
+  fn list_bodies_round_trip() {
+    round_trip(Body::FileList(vec![
+      FileEntry::new("/tmp/a.txt"),
+      FileEntry {
+        path: PathBuf::from("/tmp/b.png"),
+        metadata: Some(FileMetadata {
+          exists: true,
+          is_dir: false,
+          size: Some(42),
+          modified: Some(SystemTime::UNIX_EPOCH),
+          mime_type: Some("image/png".to_string()),
+        }),
+      },
+    ]));
+    round_trip(Body::UriList(vec![
+      Url::parse("https://example.com").unwrap(),
+    ]));
+  }
+
+  #[test]
+  fn binary_bodies_round_trip() {
+    round_trip(Body::Custom {
+      name: "application/x-custom".into(),
+      data: Bytes::from_static(b"\x00\x01\x02"),
+    });
+    round_trip(Body::Image(ClipboardImage {
+      bytes: Bytes::from_static(b"\x89PNG"),
+      mime_type: MimeType::Png,
+      width: 1,
+      height: 2,
+      color_type: ColorType::Rgba8,
+      thumbnail: None,
+      path: Some(PathBuf::from("/tmp/image.png")),
+    }));
+  }
+}
@@ -0,0 +1,874 @@
+//! Classification of text content by what it represents, such as a link or a color code.
+
+use std::{fmt, path::PathBuf, sync::Arc};
+
+use url::Url;
+
+use crate::{Body, ClipboardContent};
+
+/// What a text item represents, as found by [`classify`](crate::ClipboardEventListenerBuilder::classify).
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ContentKind {
+  /// A web link, or any other URL with a host.
+  Url(Url),
+  /// An e-mail address, without the `mailto:` scheme.
+  Email(String),
+  /// An absolute path, or a `file://` URL converted to a path.
+  Path(PathBuf),
+  /// A color code, either hexadecimal (`#ff8800`) or functional (`rgb(255, 136, 0)`).
+  Color(Rgba),
+  /// A JSON object or array.
+  Json,
+  /// A number, with the digit separators removed so that it can be parsed with [`str::parse`].
+  Number(String),
+  /// A phone number, with only its digits and the leading `+` (if present).
+  PhoneNumber(String),
+  /// Source code, as guessed from its syntax.
+  Code,
+  /// A kind defined by a custom [`Classifier`].
+  Custom {
+    name: Arc<str>,
+    value: Option<String>,
+  },
+}
+
+/// A color, as parsed from a color code.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rgba {
+  pub r: u8,
+  pub g: u8,
+  pub b: u8,
+  pub a: u8,
+}
+
+/// Finds what a text item represents, added with [`classifier`](crate::ClipboardEventListenerBuilder::classifier).
+///
+/// Custom classifiers run before the built-in ones, in the order they are added, and the first match is used.
+/// They are also implemented for closures.
+///
+/// # Example
+/// ```
+/// use std::sync::Arc;
+///
+/// use clipboard_watcher::{ClipboardEventListener, ContentKind};
+///
+/// let builder = ClipboardEventListener::builder().classifier(|text: &str| {
+///     text.starts_with("ISSUE-").then(|| ContentKind::Custom {
+///         name: Arc::from("issue"),
+///         value: Some(text.trim_start_matches("ISSUE-").to_string()),
+///     })
+/// });
+/// ```
+pub trait Classifier: Send + Sync + 'static {
+  /// Returns the kind of the text, or `None` to let the next classifier try.
+  fn classify(&self, text: &str) -> Option<ContentKind>;
+}
+
+impl<F> Classifier for F
+where
+  F: Fn(&str) -> Option<ContentKind> + Send + Sync + 'static,
+{
+  fn classify(&self, text: &str) -> Option<ContentKind> {
+    self(text)
+  }
+}
+
+/// The classifiers selected for a listener.
+#[derive(Clone, Default)]
+pub(crate) struct Classifiers {
+  pub(crate) builtin: bool,
+  pub(crate) custom: Vec<Arc<dyn Classifier>>,
+}
+
+impl fmt::Debug for Classifiers {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("Classifiers")
+      .field("builtin", &self.builtin)
+      .field("custom", &self.custom.len())
+      .finish()
+  }
+}
+
+impl Classifiers {
+  pub(crate) fn is_enabled(&self) -> bool {
+    self.builtin || !self.custom.is_empty()
+  }
+
+  /// Classifies the text of some content, preferring the plain text over the HTML.
+  ///
+  /// Lazy, skipped and concealed content is not classified, since it has not been read.
+  pub(crate) fn classify(&self, content: &ClipboardContent) -> Option<ContentKind> {
+    let text = match content {
+      ClipboardContent::Body(body) => match body.as_ref() {
+        Body::PlainText(text) => text.clone(),
+        Body::Html(html) => html_to_text(&html.fragment),
+        _ => return None,
+      },
+      ClipboardContent::Snapshot(snapshot) => match (snapshot.plain_text(), snapshot.html()) {
+        (Some(text), _) => text.to_string(),
+        (None, Some(html)) => html_to_text(&html.fragment),
+        (None, None) => return None,
+      },
+      ClipboardContent::Lazy(_) | ClipboardContent::Skipped(_) | ClipboardContent::Concealed => {
+        return None;
+      }
+    };
+
+    let text = text.trim();
+
+    if text.is_empty() {
+      return None;
+    }
+
+    self
+      .custom
+      .iter()
+      .find_map(|classifier| classifier.classify(text))
+      .or_else(|| self.builtin.then(|| classify_text(text)).flatten())
+  }
+}
+
+/// Extracts the text of some HTML markup, by dropping the tags and decoding the common entities.
+fn html_to_text(html: &str) -> String {
+  let mut text = String::with_capacity(html.len());
+  let mut in_tag = false;
+
+  for c in html.chars() {
+    match c {
+      '<' => in_tag = true,
+      '>' if in_tag => in_tag = false,
+      c if !in_tag => text.push(c),
+      _ => {}
+    }
+  }
+
+  text
+    .replace("&nbsp;", " ")
+    .replace("&lt;", "<")
+    .replace("&gt;", ">")
+    .replace("&quot;", "\"")
+    .replace("&#39;", "'")
+    .replace("&amp;", "&")
+}
+
+/// Runs the built-in classifiers on some trimmed text.
+fn classify_text(text: &str) -> Option<ContentKind> {
+  let single_word = !text.contains(char::is_whitespace);
+
+  if single_word && let Some(kind) = classify_url(text) {
+    return Some(kind);
+  }
+
+  if single_word && is_email(text) {
+    return Some(ContentKind::Email(text.to_string()));
+  }
+
+  if !text.contains('\n') && is_path(text) {
+    return Some(ContentKind::Path(PathBuf::from(text)));
+  }
+
+  if let Some(color) = parse_color(text) {
+    return Some(ContentKind::Color(color));
+  }
+
+  if (text.starts_with('{') || text.starts_with('[')) && is_json(text) {
+    return Some(ContentKind::Json);
+  }
+
+  if let Some(number) = parse_number(text) {
+    return Some(ContentKind::Number(number));
+  }
+
+  if let Some(phone) = parse_phone_number(text) {
+    return Some(ContentKind::PhoneNumber(phone));
+  }
+
+  if is_code(text) {
+    return Some(ContentKind::Code);
+  }
+
+  None
+}
+
+fn classify_url(text: &str) -> Option<ContentKind> {
+  // Links are often copied without their scheme
+  let url = if text.starts_with("www.") {
+    Url::parse(&format!("https://{text}")).ok()?
+  } else {
+    Url::parse(text).ok()?
+  };
+
+  match url.scheme() {
+    "file" => url.to_file_path().ok().map(ContentKind::Path),
+    "mailto" => Some(url.path())
+      .filter(|address| is_email(address))
+      .map(|address| ContentKind::Email(address.to_string())),
+    _ if url.host_str().is_some_and(|host| !host.is_empty()) => Some(ContentKind::Url(url)),
+    _ => None,
+  }
+}
+
+fn is_email(text: &str) -> bool {
+  let Some((local, domain)) = text.split_once('@') else {
+    return false;
+  };
+
+  let valid_label = |label: &str| {
+    !label.is_empty()
+      && !label.starts_with('-')
+      && !label.ends_with('-')
+      && label.chars().all(|c| c.is_alphanumeric() || c == '-')
+  };
+
+  !local.is_empty()
+    && !local.starts_with('.')
+    && !local.ends_with('.')
+    && local
+      .chars()
+      .all(|c| c.is_alphanumeric() || "!#$%&'*+-/=?^_`{|}~.".contains(c))
+    && domain.contains('.')
+    && domain.split('.').all(valid_label)
+}
+
+fn is_path(text: &str) -> bool {
+  let unix =
+    (text.starts_with('/') && text.len() > 1 && !text.starts_with("//")) || text.starts_with("~/");
+
+  let mut chars = text.chars();
+  let windows_drive = matches!(
+    (chars.next(), chars.next(), chars.next()),
+    (Some(drive), Some(':'), Some('\\' | '/')) if drive.is_ascii_alphabetic()
+  );
+  let windows_share = text.starts_with("\\\\") && text.len() > 2;
+
+  (unix || windows_drive || windows_share) && !text.contains(['<', '>', '"', '|', '*', '?'])
+}
+
+fn parse_color(text: &str) -> Option<Rgba> {
+  if let Some(hex) = text.strip_prefix('#') {
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+      return None;
+    }
+
+    let digit = |i: usize| u8::from_str_radix(&hex[i..=i], 16).ok();
+    let pair = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+
+    return match hex.len() {
+      3 | 4 => Some(Rgba {
+        r: digit(0)? * 17,
+        g: digit(1)? * 17,
+        b: digit(2)? * 17,
+        a: if hex.len() == 4 { digit(3)? * 17 } else { 255 },
+      }),
+      6 | 8 => Some(Rgba {
+        r: pair(0)?,
+        g: pair(2)?,
+        b: pair(4)?,
+        a: if hex.len() == 8 { pair(6)? } else { 255 },
+      }),
+      _ => None,
+    };
+  }
+
+  let lowercase = text.to_ascii_lowercase();
+  let arguments = lowercase
+    .strip_prefix("rgba(")
+    .or_else(|| lowercase.strip_prefix("rgb("))?
+    .strip_suffix(')')?;
+
+  let values: Vec<&str> = arguments
+    .split([',', ' ', '/'])
+    .filter(|value| !value.is_empty())
+    .collect();
+
+  let channel = |value: &str| value.trim().parse::<u8>().ok();
+  // The alpha channel is either a fraction or a percentage
+  let alpha = |value: &str| {
+    let alpha = match value.strip_suffix('%') {
+      Some(percentage) => percentage.parse::<f32>().ok()? / 100.0,
+      None => value.parse::<f32>().ok()?,
+    };
+
+    (0.0..=1.0)
+      .contains(&alpha)
+      .then(|| (alpha * 255.0).round() as u8)
+  };
+
+  match values.as_slice() {
+    [r, g, b] => Some(Rgba {
+      r: channel(r)?,
+      g: channel(g)?,
+      b: channel(b)?,
+      a: 255,
+    }),
+    [r, g, b, a] => Some(Rgba {
+      r: channel(r)?,
+      g: channel(g)?,
+      b: channel(b)?,
+      a: alpha(a)?,
+    }),
+    _ => None,
+  }
+}
+
+/// A minimal JSON validator, which checks the syntax without building the value.
+struct JsonValidator<'a> {
+  bytes: &'a [u8],
+  position: usize,
+}
+
+impl JsonValidator<'_> {
+  fn skip_whitespace(&mut self) {
+    while self
+      .bytes
+      .get(self.position)
+      .is_some_and(|b| b.is_ascii_whitespace())
+    {
+      self.position += 1;
+    }
+  }
+
+  fn eat(&mut self, byte: u8) -> bool {
+    self.skip_whitespace();
+
+    if self.bytes.get(self.position) == Some(&byte) {
+      self.position += 1;
+      true
+    } else {
+      false
+    }
+  }
+
+  fn literal(&mut self, literal: &str) -> bool {
+    if self.bytes[self.position..].starts_with(literal.as_bytes()) {
+      self.position += literal.len();
+      true
+    } else {
+      false
+    }
+  }
+
+  fn string(&mut self) -> bool {
+    if !self.eat(b'"') {
+      return false;
+    }
+
+    while let Some(&b) = self.bytes.get(self.position) {
+      self.position += 1;
+
+      match b {
+        b'"' => return true,
+        // The escaped character is skipped
+        b'\\' => self.position += 1,
+        b if b < 0x20 => return false,
+        _ => {}
+      }
+    }
+
+    false
+  }
+
+  fn number(&mut self) -> bool {
+    let start = self.position;
+
+    while self
+      .bytes
+      .get(self.position)
+      .is_some_and(|b| b.is_ascii_digit() || b"+-.eE".contains(b))
+    {
+      self.position += 1;
+    }
+
+    std::str::from_utf8(&self.bytes[start..self.position])
+      .is_ok_and(|number| number.parse::<f64>().is_ok())
+  }
+
+  fn value(&mut self, depth: usize) -> bool {
+    // Protects the stack from deeply nested input
+    if depth > 128 {
+      return false;
+    }
+
+    self.skip_whitespace();
+
+    match self.bytes.get(self.position) {
+      Some(b'{') => {
+        self.position += 1;
+
+        if self.eat(b'}') {
+          return true;
+        }
+
+        loop {
+          if !(self.string() && self.eat(b':') && self.value(depth + 1)) {
+            return false;
+          }
+
+          if self.eat(b'}') {
+            return true;
+          }
+
+          if !self.eat(b',') {
+            return false;
+          }
+        }
+      }
+      Some(b'[') => {
+        self.position += 1;
+
+        if self.eat(b']') {
+          return true;
+        }
+
+        loop {
+          if !self.value(depth + 1) {
+            return false;
+          }
+
+          if self.eat(b']') {
+            return true;
+          }
+
+          if !self.eat(b',') {
+            return false;
+          }
+        }
+      }
+      Some(b'"') => self.string(),
+      Some(b't') => self.literal("true"),
+      Some(b'f') => self.literal("false"),
+      Some(b'n') => self.literal("null"),
+      Some(_) => self.number(),
+      None => false,
+    }
+  }
+}
+
+fn is_json(text: &str) -> bool {
+  let mut validator = JsonValidator {
+    bytes: text.as_bytes(),
+    position: 0,
+  };
+
+  let valid = validator.value(0);
+  validator.skip_whitespace();
+
+  valid && validator.position == text.len()
+}
+
+fn parse_number(text: &str) -> Option<String> {
+  let (sign, digits) = match text.strip_prefix(['-', '+']) {
+    Some(digits) => (&text[..1], digits),
+    None => ("", text),
+  };
+
+  let (integer, fraction) = match digits.split_once('.') {
+    Some((integer, fraction)) => (integer, Some(fraction)),
+    None => (digits, None),
+  };
+
+  // Digit separators are only allowed between groups of three digits
+  let separator = integer.chars().find(|c| matches!(c, ',' | '_' | ' '));
+  let groups_valid = match separator {
+    Some(separator) => {
+      let mut groups = integer.split(separator);
+      groups
+        .next()
+        .is_some_and(|first| (1..=3).contains(&first.len()))
+        && groups.all(|group| group.len() == 3)
+    }
+    None => true,
+  };
+
+  let integer: String = integer
+    .chars()
+    .filter(|c| !matches!(c, ',' | '_' | ' '))
+    .collect();
+
+  let valid = groups_valid
+    && !integer.is_empty()
+    && integer.chars().all(|c| c.is_ascii_digit())
+    && fraction
+      .is_none_or(|fraction| !fraction.is_empty() && fraction.chars().all(|c| c.is_ascii_digit()));
+
+  valid.then(|| match fraction {
+    Some(fraction) => format!("{sign}{integer}.{fraction}"),
+    None => format!("{sign}{integer}"),
+  })
+}
+
+fn parse_phone_number(text: &str) -> Option<String> {
+  if !text
+    .chars()
+    .all(|c| c.is_ascii_digit() || " +-().".contains(c))
+    || text.chars().skip(1).any(|c| c == '+')
+  {
+    return None;
+  }
+
+  let digits: String = text.chars().filter(char::is_ascii_digit).collect();
+
+  (7..=15).contains(&digits.len()).then(|| {
+    if text.starts_with('+') {
+      format!("+{digits}")
+    } else {
+      digits
+    }
+  })
+}
+
+const CODE_KEYWORDS: [&str; 16] = [
+  "fn ",
+  "let ",
+  "const ",
+  "function ",
+  "def ",
+  "class ",
+  "import ",
+  "return ",
+  "#include",
+  "public ",
+  "private ",
+  "struct ",
+  "impl ",
+  "var ",
+  "package ",
+  "=>",
+];
+
+/// Guesses whether some text is source code, from the share of lines that look like code.
+fn is_code(text: &str) -> bool {
+  let lines: Vec<&str> = text
+    .lines()
+    .map(str::trim)
+    .filter(|line| !line.is_empty())
+    .collect();
+
+  if lines.len() < 2 {
+    return false;
+  }
+
+  let code_lines = lines
+    .iter()
+    .filter(|line| {
+      line.ends_with([';', '{', '}', ')', ':'])
+        || line.starts_with("//")
+        || CODE_KEYWORDS.iter().any(|keyword| line.contains(keyword))
+    })
+    .count();
+
+  code_lines * 2 >= lines.len()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{ClipboardSnapshot, HtmlContent, SkipReason, SkippedContent};
+
+  fn url(url: &str) -> ContentKind {
+    ContentKind::Url(Url::parse(url).unwrap())
+  }
+
+  fn number(number: &str) -> Option<ContentKind> {
+    Some(ContentKind::Number(number.to_string()))
+  }
+
+  fn phone(phone: &str) -> Option<ContentKind> {
+    Some(ContentKind::PhoneNumber(phone.to_string()))
+  }
+
+  fn color(r: u8, g: u8, b: u8, a: u8) -> Option<ContentKind> {
+    Some(ContentKind::Color(Rgba { r, g, b, a }))
+  }
+
+  #[test]
+  fn urls() {
+    assert_eq!(
+      classify_text("https://example.com/a?b=c"),
+      Some(url("https://example.com/a?b=c"))
+    );
+    assert_eq!(
+      classify_text("www.example.com"),
+      Some(url("https://www.example.com"))
+    );
+    assert_eq!(
+      classify_text("sftp://host/file"),
+      Some(url("sftp://host/file"))
+    );
+    // Schemes without a host are not links
+    assert_eq!(classify_text("urn:isbn:0451450523"), None);
+    assert_eq!(classify_text("localhost:8080"), None);
+    // Links must be on their own
+    assert_eq!(classify_text("see https://example.com"), None);
+  }
+
+  #[test]
+  fn emails() {
+    let email = |address: &str| Some(ContentKind::Email(address.to_string()));
+
+    assert_eq!(
+      classify_text("first.last+tag@example.co.uk"),
+      email("first.last+tag@example.co.uk")
+    );
+    assert_eq!(
+      classify_text("mailto:me@example.com"),
+      email("me@example.com")
+    );
+    assert_eq!(classify_text("me@localhost"), None);
+    assert_eq!(classify_text(".me@example.com"), None);
+    assert_eq!(classify_text("me.@example.com"), None);
+    assert_eq!(classify_text("me@-example.com"), None);
+    assert_eq!(classify_text("me@example..com"), None);
+    assert_eq!(classify_text("@example.com"), None);
+  }
+
+  #[test]
+  fn paths() {
+    let path = |path: &str| Some(ContentKind::Path(PathBuf::from(path)));
+
+    assert_eq!(classify_text("/usr/bin/env"), path("/usr/bin/env"));
+    assert_eq!(
+      classify_text("~/Documents/a b.txt"),
+      path("~/Documents/a b.txt")
+    );
+    assert_eq!(
+      classify_text("C:\\Windows\\System32"),
+      path("C:\\Windows\\System32")
+    );
+    assert_eq!(
+      classify_text("\\\\server\\share"),
+      path("\\\\server\\share")
+    );
+    #[cfg(unix)]
+    assert_eq!(classify_text("file:///tmp/a%20b.txt"), path("/tmp/a b.txt"));
+
+    assert_eq!(classify_text("/"), None);
+    assert_eq!(classify_text("//comment"), None);
+    assert_eq!(classify_text("/tmp/*.txt"), None);
+    assert_eq!(classify_text("/a\n/b"), None);
+  }
+
+  #[test]
+  fn hex_colors() {
+    assert_eq!(classify_text("#fff"), color(255, 255, 255, 255));
+    assert_eq!(classify_text("#0f08"), color(0, 255, 0, 136));
+    assert_eq!(classify_text("#FF8800"), color(255, 136, 0, 255));
+    assert_eq!(classify_text("#ff880080"), color(255, 136, 0, 128));
+
+    assert_eq!(
+      parse_color("#ff88"),
+      Some(Rgba {
+        r: 255,
+        g: 255,
+        b: 136,
+        a: 136
+      })
+    );
+    assert_eq!(parse_color("#ff880"), None);
+    assert_eq!(parse_color("#ggg"), None);
+    assert_eq!(parse_color("#"), None);
+    assert_eq!(parse_color("#ééé"), None);
+  }
+
+  #[test]
+  fn functional_colors() {
+    assert_eq!(classify_text("rgb(255, 136, 0)"), color(255, 136, 0, 255));
+    assert_eq!(classify_text("RGB(1,2,3)"), color(1, 2, 3, 255));
+    assert_eq!(
+      classify_text("rgba(255, 136, 0, 0.5)"),
+      color(255, 136, 0, 128)
+    );
+    assert_eq!(classify_text("rgb(0 0 0 / 25%)"), color(0, 0, 0, 64));
+
+    assert_eq!(parse_color("rgb(256, 0, 0)"), None);
+    assert_eq!(parse_color("rgba(0, 0, 0, 1.5)"), None);
+    assert_eq!(parse_color("rgb(0, 0)"), None);
+    assert_eq!(parse_color("rgb(0, 0, 0"), None);
+  }
+
+  #[test]
+  fn json() {
+    assert_eq!(
+      classify_text(r#"{"a": [1, -2.5e3, true, null], "b": {"c": "\"x\""}}"#),
+      Some(ContentKind::Json)
+    );
+    assert_eq!(classify_text("[]"), Some(ContentKind::Json));
+    assert_eq!(classify_text("{ }"), Some(ContentKind::Json));
+
+    assert!(!is_json(r#"{"a": 1,}"#));
+    assert!(!is_json(r#"{"a" 1}"#));
+    assert!(!is_json(r#"{a: 1}"#));
+    assert!(!is_json("[1, 2] 3"));
+    assert!(!is_json("[tru]"));
+    assert!(!is_json("[\"a\nb\"]"));
+    assert!(!is_json("[\"unterminated]"));
+    assert!(!is_json("[-]"));
+  }
+
+  #[test]
+  fn deeply_nested_json() {
+    let nested = format!("{}{}", "[".repeat(10_000), "]".repeat(10_000));
+
+    assert!(!is_json(&nested));
+  }
+
+  #[test]
+  fn numbers() {
+    assert_eq!(classify_text("42"), number("42"));
+    assert_eq!(classify_text("-3.14"), number("-3.14"));
+    assert_eq!(classify_text("+7"), number("+7"));
+    assert_eq!(classify_text("1,234,567.89"), number("1234567.89"));
+    assert_eq!(classify_text("1_000"), number("1000"));
+    assert_eq!(classify_text("12 345"), number("12345"));
+
+    assert_eq!(parse_number("1,23"), None);
+    assert_eq!(parse_number("1234,567"), None);
+    assert_eq!(parse_number(",123"), None);
+    assert_eq!(parse_number("1,000_000"), None);
+    assert_eq!(parse_number("1."), None);
+    assert_eq!(parse_number(".5"), None);
+    assert_eq!(parse_number("-"), None);
+    assert_eq!(parse_number("1.2.3"), None);
+    assert_eq!(parse_number("١٢٣"), None);
+  }
+
+  #[test]
+  fn phone_numbers() {
+    assert_eq!(classify_text("+1 (555) 123-4567"), phone("+15551234567"));
+    assert_eq!(classify_text("06 12 34 56 78"), phone("0612345678"));
+    assert_eq!(classify_text("555.123.4567"), phone("5551234567"));
+
+    assert_eq!(parse_phone_number("123-45"), None);
+    assert_eq!(parse_phone_number("1234567890123456"), None);
+    assert_eq!(parse_phone_number("555+1234567"), None);
+    assert_eq!(parse_phone_number("555-CALL-NOW"), None);
+  }
+
+  #[test]
+  fn code() {
+    assert_eq!(
+      classify_text("fn main() {\n    println!(\"Hello\");\n}"),
+      Some(ContentKind::Code)
+    );
+    assert_eq!(
+      classify_text("def greet(name):\n    return f\"Hi {name}\""),
+      Some(ContentKind::Code)
+    );
+
+    assert_eq!(classify_text("let me know"), None);
+    assert_eq!(
+      classify_text("Dear all,\nthe meeting is moved to Friday.\nThanks"),
+      None
+    );
+  }
+
+  #[test]
+  fn html_text() {
+    assert_eq!(
+      html_to_text("<p>Tom &amp; Jerry&nbsp;&lt;3</p>"),
+      "Tom & Jerry <3"
+    );
+    // Entities are only decoded once
+    assert_eq!(html_to_text("&amp;lt;"), "&lt;");
+  }
+
+  fn classifiers(builtin: bool) -> Classifiers {
+    Classifiers {
+      builtin,
+      custom: Vec::new(),
+    }
+  }
+
+  fn body(body: Body) -> ClipboardContent {
+    ClipboardContent::Body(Arc::new(body))
+  }
+
+  fn html(fragment: &str) -> Body {
+    Body::Html(HtmlContent {
+      fragment: fragment.to_string(),
+      document: None,
+      source_url: None,
+    })
+  }
+
+  #[test]
+  fn classifies_text_and_html() {
+    let classifiers = classifiers(true);
+
+    assert_eq!(
+      classifiers.classify(&body(Body::PlainText("  42\n".to_string()))),
+      number("42")
+    );
+    assert_eq!(
+      classifiers.classify(&body(html("<a href=\"x\">https://example.com</a>"))),
+      Some(url("https://example.com"))
+    );
+    assert_eq!(
+      classifiers.classify(&body(Body::PlainText(" \n ".to_string()))),
+      None
+    );
+    assert_eq!(
+      classifiers.classify(&body(Body::Rtf("42".to_string()))),
+      None
+    );
+  }
+
+  #[test]
+  fn snapshots_prefer_plain_text() {
+    let snapshot =
+      ClipboardSnapshot::new(vec![html("<b>#fff</b>"), Body::PlainText("42".to_string())]);
+
+    assert_eq!(
+      classifiers(true).classify(&ClipboardContent::Snapshot(Arc::new(snapshot))),
+      number("42")
+    );
+  }
+
+  #[test]
+  fn content_that_was_not_read_is_not_classified() {
+    let skipped = ClipboardContent::Skipped(SkippedContent {
+      reason: SkipReason::Empty,
+      format: None,
+      size: None,
+    });
+
+    assert_eq!(classifiers(true).classify(&skipped), None);
+    assert_eq!(
+      classifiers(true).classify(&ClipboardContent::Concealed),
+      None
+    );
+  }
+
+  #[test]
+  fn custom_classifiers_run_first() {
+    let mut classifiers = classifiers(true);
+    classifiers.custom.push(Arc::new(|text: &str| {
+      (text == "42").then(|| ContentKind::Custom {
+        name: Arc::from("answer"),
+        value: None,
+      })
+    }));
+
+    assert_eq!(
+      classifiers.classify(&body(Body::PlainText("42".to_string()))),
+      Some(ContentKind::Custom {
+        name: Arc::from("answer"),
+        value: None,
+      })
+    );
+    // Other text falls through to the built-in classifiers
+    assert_eq!(
+      classifiers.classify(&body(Body::PlainText("43".to_string()))),
+      number("43")
+    );
+
+    classifiers.builtin = false;
+
+    assert_eq!(
+      classifiers.classify(&body(Body::PlainText("43".to_string()))),
+      None
+    );
+  }
+}
@@ -1,16 +1,119 @@
+#[cfg(all(unix, not(target_os = "macos")))]
+use std::sync::mpsc;
 use std::{
   sync::{
-    atomic::{AtomicBool, Ordering},
     Arc,
+    atomic::{AtomicBool, Ordering},
   },
   thread::JoinHandle,
 };
 
+#[cfg(all(unix, not(target_os = "macos")))]
+use log::debug;
+
+use crate::{Backend, backend::ClipboardBackend, sink::ClipboardSink};
+#[cfg(all(unix, not(target_os = "macos")))]
+use crate::{error::ClipboardError, extraction::Extractor, observer::Observer};
+
 /// An event driver that monitors clipboard updates and notify
 #[derive(Debug)]
 pub(crate) struct Driver {
   pub(crate) stop: Arc<AtomicBool>,
   pub(crate) handle: Option<JoinHandle<()>>,
+  pub(crate) backend: Backend,
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+impl Driver {
+  /// Construct [`Driver`] with the first backend that can be started,
+  /// trying Wayland first (which also covers XWayland sessions), then X11 with XFixes, and then X11 with polling.
+  pub(crate) fn new(sink: ClipboardSink, extractor: Extractor) -> Result<Self, ClipboardError> {
+    let mut failures = Vec::new();
+
+    for backend in [
+      Backend::WaylandDataControl,
+      Backend::X11XFixes,
+      Backend::X11Polling,
+    ] {
+      let result = match backend {
+        Backend::WaylandDataControl => Self::new_wayland(sink.clone(), extractor.clone()),
+        _ => Self::new_x11(backend, sink.clone(), extractor.clone()),
+      };
+
+      match result {
+        Ok(driver) => {
+          debug!("Using the {backend} backend");
+          return Ok(driver);
+        }
+        // The backend is not supported in this session, try the next one
+        Err(ClipboardError::InitializationError(reason)) => {
+          debug!("The {backend} backend is not available: {reason}");
+          failures.push(ClipboardError::BackendUnavailable { backend, reason });
+        }
+        Err(e) => return Err(e),
+      }
+    }
+
+    Err(ClipboardError::NoBackendAvailable(failures))
+  }
+
+  /// Spawns a thread that creates an observer with `init` and runs it,
+  /// blocking until the observer has been initialized.
+  pub(crate) fn spawn<O, F>(
+    backend: Backend,
+    sink: ClipboardSink,
+    init: F,
+  ) -> Result<Self, ClipboardError>
+  where
+    O: Observer,
+    F: FnOnce(Arc<AtomicBool>) -> Result<O, ClipboardError> + Send + 'static,
+  {
+    let stop = sink.stop_flag();
+
+    let stop_cl = stop.clone();
+
+    let (init_tx, init_rx) = mpsc::sync_channel(0);
+
+    // spawn OS thread
+    // observe clipboard change event and send item
+    let handle = std::thread::spawn(move || match init(stop_cl) {
+      Ok(mut observer) => {
+        init_tx.send(Ok(())).unwrap();
+
+        // event change observe loop
+        observer.observe(sink);
+      }
+      Err(e) => {
+        init_tx.send(Err(e)).unwrap();
+      }
+    });
+
+    // Block until we get an init signal
+    match init_rx.recv() {
+      Ok(Ok(())) => Ok(Driver {
+        stop,
+        handle: Some(handle),
+        backend,
+      }),
+      Ok(Err(e)) => Err(e),
+      Err(e) => Err(ClipboardError::TryRecvError(e.to_string())),
+    }
+  }
+}
+
+impl Driver {
+  /// Construct [`Driver`] and spawn a thread that runs a custom backend
+  pub(crate) fn custom(mut backend: Box<dyn ClipboardBackend>, sink: ClipboardSink) -> Self {
+    let stop = sink.stop_flag();
+
+    let handle = std::thread::spawn(move || backend.observe(sink));
+
+    Driver {
+      stop,
+      handle: Some(handle),
+      backend: Backend::Custom,
+    }
+  }
 }
 
 impl Drop for Driver {
@@ -1,8 +1,6 @@
-use std::sync::Arc;
-
 use thiserror::Error;
 
-use crate::Body;
+use crate::{Backend, ClipboardEvent, FormatKind, SecretKind, SkipReason, SkippedContent};
 
 /// Various kinds of errors that can occur while monitoring or reading the clipboard.
 #[derive(Clone, Debug, Error)]
@@ -10,6 +8,13 @@ pub enum ClipboardError {
   #[error("Failed to start clipboard monitor: {0}")]
   InitializationError(String),
 
+  #[error("The {backend} backend is not available: {reason}")]
+  BackendUnavailable { backend: Backend, reason: String },
+
+  /// None of the backends could be started. Holds the reason why each of them failed.
+  #[error("No clipboard backend is available: {}", display_failures(.0))]
+  NoBackendAvailable(Vec<ClipboardError>),
+
   #[error("Failed to monitor the clipboard: {0}")]
   MonitorFailed(String),
 
@@ -19,17 +24,68 @@ pub enum ClipboardError {
   #[error("Failed to read the clipboard: {0}")]
   ReadError(String),
 
+  #[error("Failed to write to the clipboard: {0}")]
+  WriteError(String),
+
+  #[error("The {0} backend does not support this operation")]
+  Unsupported(Backend),
+
   #[error("The content of the clipboard did not match any supported format")]
   NoMatchingFormat,
 
-  #[error("Could not convert clipboard image to png format")]
+  #[error("Could not convert clipboard image to the target format")]
   ImageConversion,
+
+  /// The clipboard text was not valid in its encoding, with the [`Strict`](crate::TextDecoding::Strict) decoding.
+  #[error("The clipboard text is not valid in its encoding")]
+  InvalidText,
+
+  /// The clipboard changed before some [`LazyContent`](crate::LazyContent) could be fetched.
+  #[error("The clipboard content changed before it could be fetched")]
+  ContentChanged,
+
+  /// Some [`LazyContent`](crate::LazyContent) held secrets when it was fetched, so it was withheld as set with
+  /// [`SecretAction::Redact`](crate::SecretAction::Redact). Holds the kinds of the secrets.
+  #[error("The clipboard content holds a secret, so it was withheld")]
+  SecretFound(Vec<SecretKind>),
+}
+
+fn display_failures(failures: &[ClipboardError]) -> String {
+  failures
+    .iter()
+    .map(ToString::to_string)
+    .collect::<Vec<_>>()
+    .join("; ")
 }
 
 pub(crate) enum ExtractionError {
   EmptyContent,
-  SizeTooLarge,
+  /// Holds the size of the content, if it is known.
+  SizeTooLarge(Option<usize>),
   ConversionError,
+  InvalidText,
+  /// Content that was found but not used, in the format with which it was found.
+  Skipped(SkippedContent),
+  // Only the backends that talk to a display server can fail while reading
+  #[cfg_attr(not(all(unix, not(target_os = "macos"))), allow(dead_code))]
+  ReadError(String),
+}
+
+impl ExtractionError {
+  /// Attaches the format that was being read to the errors that make the content skipped.
+  pub(crate) fn in_format(self, format: FormatKind) -> Self {
+    let (reason, size) = match self {
+      ExtractionError::EmptyContent => (SkipReason::Empty, None),
+      ExtractionError::SizeTooLarge(size) => (SkipReason::TooLarge, size),
+      e => return e,
+    };
+
+    ExtractionError::Skipped(SkippedContent {
+      reason,
+      format: Some(format),
+      size,
+    })
+  }
 }
 
-pub type ClipboardResult = Result<Arc<Body>, ClipboardError>;
+pub type ClipboardResult = Result<ClipboardEvent, ClipboardError>;
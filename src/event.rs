@@ -0,0 +1,212 @@
+use std::{
+  sync::Arc,
+  time::{Instant, SystemTime},
+};
+
+use crate::{Body, ClipboardSnapshot, ContentKind, FormatKind, LazyContent, SecretKind};
+
+/// The kind of clipboard that a change comes from.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ClipboardKind {
+  /// The regular clipboard, used by the copy and paste actions.
+  #[default]
+  Clipboard,
+  /// The primary selection, which holds the last selected text and is pasted with the middle click.
+  ///
+  /// It is only available on Linux (X11 and Wayland).
+  Primary,
+}
+
+/// The content of a clipboard change.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ClipboardContent {
+  /// The content in the format with the highest priority. See [`Body`] for more information.
+  Body(Arc<Body>),
+  /// Every available representation of the content, when [`snapshots`](crate::ClipboardEventListenerBuilder::snapshots) are enabled.
+  Snapshot(Arc<ClipboardSnapshot>),
+  /// The formats in which the content is available, to be fetched on demand, when [`lazy`](crate::ClipboardEventListenerBuilder::lazy) mode is enabled.
+  ///
+  /// It cannot be serialized, since it refers to the current content of the clipboard.
+  #[cfg_attr(feature = "serde", serde(skip))]
+  Lazy(Arc<LazyContent>),
+  /// Content that was found but not captured, when [`report_skipped`](crate::ClipboardEventListenerBuilder::report_skipped) is enabled.
+  Skipped(SkippedContent),
+  /// Content that was withheld because it holds a secret: either it was marked as such by a password manager
+  /// and the [`ConcealedPolicy`] is [`Redact`](ConcealedPolicy::Redact) (in which case it is never read),
+  /// or a secret was found in it and the [`SecretAction`](crate::SecretAction) is [`Redact`](crate::SecretAction::Redact).
+  Concealed,
+}
+
+/// How to handle the content that password managers mark as secret.
+///
+/// The markers are the `x-kde-passwordManagerHint` target on Linux, the `org.nspasteboard.ConcealedType` and
+/// `org.nspasteboard.TransientType` types on macOS, and the `ExcludeClipboardContentFromMonitorProcessing` format on Windows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ConcealedPolicy {
+  /// The change is ignored, as if it never happened.
+  #[default]
+  Drop,
+  /// A [`Concealed`](ClipboardContent::Concealed) event is sent, without the content.
+  Redact,
+  /// The content is captured like any other.
+  Capture,
+}
+
+/// Why the content of a clipboard change was not captured.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SkipReason {
+  /// The content was empty.
+  Empty,
+  /// The content was beyond the allowed size, set with [`max_size`](crate::ClipboardEventListenerBuilder::max_size)
+  /// or [`max_image_size`](crate::ClipboardEventListenerBuilder::max_image_size).
+  TooLarge,
+  /// The content could not be used, such as an image that could not be converted or text that is not valid in its encoding.
+  ///
+  /// This is only reported for [snapshots](crate::ClipboardEventListenerBuilder::snapshots), which leave out such representations
+  /// instead of failing with an error.
+  Unusable,
+}
+
+/// A clipboard change whose content was not captured.
+///
+/// # Example
+/// ```no_run
+/// # use clipboard_watcher::{ClipboardContent, ClipboardEvent, SkipReason};
+/// # fn handle(event: ClipboardEvent) {
+/// if let ClipboardContent::Skipped(skipped) = &event.content
+///   && skipped.reason == SkipReason::TooLarge
+///   && let Some(size) = skipped.size
+/// {
+///     println!("Content too large ({} MB), not captured", size / 1_000_000);
+/// }
+/// # }
+/// ```
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SkippedContent {
+  pub reason: SkipReason,
+  /// The format of the content, if it is known.
+  pub format: Option<FormatKind>,
+  /// The size of the content in bytes, if it could be known without reading all of it.
+  ///
+  /// For content that is transferred in chunks, this can be a lower bound.
+  pub size: Option<usize>,
+}
+
+impl ClipboardContent {
+  /// Returns the content in the format with the highest priority.
+  ///
+  /// It returns `None` for [`Lazy`](ClipboardContent::Lazy) content, which must be fetched first, and for [`Skipped`](ClipboardContent::Skipped) or [`Concealed`](ClipboardContent::Concealed) content.
+  pub fn body(&self) -> Option<&Body> {
+    match self {
+      ClipboardContent::Body(body) => Some(body),
+      ClipboardContent::Snapshot(snapshot) => Some(snapshot.primary()),
+      ClipboardContent::Lazy(_) | ClipboardContent::Skipped(_) | ClipboardContent::Concealed => {
+        None
+      }
+    }
+  }
+}
+
+/// The application that made a clipboard change.
+///
+/// It is only detected on X11, from the properties of the window that owns the selection,
+/// so every field is optional and depends on what the application sets.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct SourceApp {
+  /// The id of the process, from the `_NET_WM_PID` property.
+  pub pid: Option<u32>,
+  /// The name of the application, from the instance part of the `WM_CLASS` property,
+  /// or the name of the process if the window has no class.
+  pub name: Option<String>,
+  /// The class of the application, from the `WM_CLASS` property.
+  pub class: Option<String>,
+}
+
+impl SourceApp {
+  /// Checks whether the name or the class of the application is the given one, ignoring case.
+  pub fn matches(&self, app: &str) -> bool {
+    [&self.name, &self.class]
+      .into_iter()
+      .flatten()
+      .any(|value| value.eq_ignore_ascii_case(app))
+  }
+}
+
+/// A clipboard change, as received by a [`ClipboardStream`](crate::ClipboardStream).
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ClipboardEvent {
+  /// The clipboard that was changed.
+  pub kind: ClipboardKind,
+  /// The content extracted from the clipboard.
+  pub content: ClipboardContent,
+  /// When the change was detected, according to the system clock.
+  pub timestamp: SystemTime,
+  /// When the change was detected, according to a monotonic clock, which makes it suitable to measure the time between changes.
+  ///
+  /// It is not serialized, since it is only meaningful in the current process.
+  #[cfg_attr(feature = "serde", serde(skip, default = "Instant::now"))]
+  pub instant: Instant,
+  /// The position of the event among the ones sent by the listener, starting from 0.
+  ///
+  /// It is shared by every kind of clipboard, so a gap in the sequence means that an event was missed.
+  pub sequence: u64,
+  /// The change counter of the system clipboard, if it has one: the `changeCount` of the pasteboard on macOS,
+  /// the clipboard sequence number on Windows, or the timestamp of the selection owner on X11.
+  pub change_counter: Option<u64>,
+  /// Whether the change was made by the listener itself, through [`set`](crate::ClipboardEventListener::set).
+  pub own_write: bool,
+  /// What the text of the content represents, when [`classify`](crate::ClipboardEventListenerBuilder::classify)
+  /// or a custom [`classifier`](crate::ClipboardEventListenerBuilder::classifier) is used.
+  ///
+  /// Only plain text and HTML content is classified.
+  pub classification: Option<ContentKind>,
+  /// The application that made the change, if it could be detected. This is currently only supported on X11.
+  pub source_app: Option<SourceApp>,
+  /// The kinds of the secrets found in the content, when [`detect_secrets`](crate::ClipboardEventListenerBuilder::detect_secrets)
+  /// or a custom [`secret_rule`](crate::ClipboardEventListenerBuilder::secret_rule) is used.
+  pub secrets: Vec<SecretKind>,
+}
+
+impl ClipboardEvent {
+  pub(crate) fn new(kind: ClipboardKind, content: ClipboardContent) -> Self {
+    ClipboardEvent {
+      kind,
+      content,
+      timestamp: SystemTime::now(),
+      instant: Instant::now(),
+      // Set by the sink when the event is sent
+      sequence: 0,
+      change_counter: None,
+      own_write: false,
+      classification: None,
+      source_app: None,
+      secrets: Vec::new(),
+    }
+  }
+
+  /// Sets the change counter of the system clipboard.
+  pub(crate) fn with_change_counter(mut self, change_counter: Option<u64>) -> Self {
+    self.change_counter = change_counter;
+    self
+  }
+
+  /// Sets the application that made the change.
+  #[cfg_attr(not(all(unix, not(target_os = "macos"))), allow(dead_code))]
+  pub(crate) fn with_source_app(mut self, source_app: Option<SourceApp>) -> Self {
+    self.source_app = source_app;
+    self
+  }
+
+  /// Returns the content in the format with the highest priority.
+  ///
+  /// This is a shortcut for [`ClipboardContent::body`].
+  pub fn body(&self) -> Option<&Body> {
+    self.content.body()
+  }
+}
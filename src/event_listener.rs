@@ -1,19 +1,25 @@
 use std::{
   sync::{
-    atomic::{AtomicUsize, Ordering},
     Arc,
+    atomic::{AtomicUsize, Ordering},
+    mpsc::{self as std_mpsc, Sender},
   },
   time::Duration,
 };
 
-use futures::channel::mpsc;
+use futures::channel::{mpsc, oneshot};
 
 use crate::error::ClipboardError;
 use crate::{
+  Backend, Body, ClipboardBackend, ClipboardKind, ClipboardStream, ConcealedPolicy, FormatKind,
+  ImageEncoding, TextDecoding,
   body::{BodySenders, BodySendersDropHandle},
+  classify::{Classifier, Classifiers},
   driver::Driver,
+  extraction::Extractor,
+  secrets::{SecretAction, SecretDetector, SecretRule},
+  sink::{ClipboardSink, Request},
   stream::StreamId,
-  ClipboardStream,
 };
 
 /// Clipboard event change listener.
@@ -23,6 +29,8 @@ use crate::{
 /// Use the [`builder`](ClipboardEventListener::builder) method to customize the options for the listener.
 pub struct ClipboardEventListener {
   driver: Option<Driver>,
+  backend: Backend,
+  requests: Sender<Request>,
   body_senders: Arc<BodySenders>,
   id: AtomicUsize,
 }
@@ -33,6 +41,24 @@ pub struct ClipboardEventListenerBuilder {
   pub(crate) custom_formats: Vec<Arc<str>>,
   pub(crate) max_image_bytes: Option<usize>,
   pub(crate) max_bytes: Option<usize>,
+  pub(crate) kinds: Vec<ClipboardKind>,
+  pub(crate) custom_backend: Option<Box<dyn ClipboardBackend>>,
+  pub(crate) ignore_own_writes: bool,
+  pub(crate) snapshots: bool,
+  pub(crate) priority: Vec<FormatKind>,
+  pub(crate) disabled_formats: Vec<FormatKind>,
+  pub(crate) ignored_apps: Vec<String>,
+  pub(crate) lazy: bool,
+  pub(crate) report_skipped: bool,
+  pub(crate) concealed: ConcealedPolicy,
+  pub(crate) rtf_to_text: bool,
+  pub(crate) keep_image_encoding: bool,
+  pub(crate) image_encoding: ImageEncoding,
+  pub(crate) thumbnail_size: Option<u32>,
+  pub(crate) text_decoding: TextDecoding,
+  pub(crate) file_metadata: Option<usize>,
+  pub(crate) classifiers: Classifiers,
+  pub(crate) secrets: SecretDetector,
 }
 
 impl ClipboardEventListenerBuilder {
@@ -46,7 +72,7 @@ impl ClipboardEventListenerBuilder {
   ///
   /// In cases where a clipboard item can match more than one format in this list, only the first will be selected.
   ///
-  /// Custom formats are always extracted with a higher priority than normal formats. See [`Body`](crate::Body) for more information about the extraction priority.
+  /// Custom formats are extracted with a higher priority than normal formats, unless a different [`priority`](Self::priority) is set. See [`Body`](crate::Body) for more information about the extraction priority.
   pub fn with_custom_formats<I, S>(mut self, formats: I) -> Self
   where
     I: IntoIterator<Item = S>,
@@ -72,19 +98,286 @@ impl ClipboardEventListenerBuilder {
     self
   }
 
+  /// Sets the order of priority used to pick the content of a clipboard item, from the highest to the lowest.
+  ///
+  /// The kinds that are left out keep their default order (see [`Body`]), after the ones that are given.
+  ///
+  /// It also sets the order of the representations in a [`ClipboardSnapshot`](crate::ClipboardSnapshot).
+  pub fn priority<I>(mut self, order: I) -> Self
+  where
+    I: IntoIterator<Item = FormatKind>,
+  {
+    self.priority.clear();
+
+    for kind in order.into_iter().chain(FormatKind::DEFAULT_PRIORITY) {
+      if !self.priority.contains(&kind) {
+        self.priority.push(kind);
+      }
+    }
+
+    self
+  }
+
+  /// Only extracts the given kinds of content, disabling all of the others.
+  ///
+  /// Disabled formats are never read from the clipboard, so a clipboard item that only has disabled formats is ignored.
+  /// The formats disabled with [`disable_formats`](Self::disable_formats) stay disabled, whichever is called first.
+  pub fn formats<I>(self, formats: I) -> Self
+  where
+    I: IntoIterator<Item = FormatKind>,
+  {
+    let enabled: Vec<FormatKind> = formats.into_iter().collect();
+
+    self.disable_formats(
+      FormatKind::DEFAULT_PRIORITY
+        .into_iter()
+        .filter(|kind| !enabled.contains(kind)),
+    )
+  }
+
+  /// Disables the given kinds of content, which are never read from the clipboard.
+  ///
+  /// For example, disabling [`Image`](FormatKind::Image) avoids decoding and converting images when only text is needed.
+  /// When [`FileList`](FormatKind::FileList) is disabled, images are not given a file path either.
+  pub fn disable_formats<I>(mut self, formats: I) -> Self
+  where
+    I: IntoIterator<Item = FormatKind>,
+  {
+    for kind in formats {
+      if !self.disabled_formats.contains(&kind) {
+        self.disabled_formats.push(kind);
+      }
+    }
+
+    self
+  }
+
+  /// Skips the changes made by the given applications, which are never read from the clipboard.
+  ///
+  /// An application is matched by the name or the class of its [`SourceApp`](crate::SourceApp), ignoring case.
+  /// Since the source application is only detected on X11, this has no effect with the other backends.
+  pub fn ignore_apps<I, S>(mut self, apps: I) -> Self
+  where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+  {
+    self
+      .ignored_apps
+      .extend(apps.into_iter().map(|s| s.as_ref().to_string()));
+    self
+  }
+
+  /// Inspects the files of each [`FileList`](crate::Body::FileList), so that their entries come with [`FileMetadata`](crate::FileMetadata):
+  /// whether they exist, their size, their modification time, whether they are directories and their MIME type, sniffed from their first bytes.
+  ///
+  /// Only the first `max_entries` files are inspected, which bounds the time spent on large lists. The other entries have no metadata.
+  pub fn file_metadata(mut self, max_entries: usize) -> Self {
+    self.file_metadata = Some(max_entries);
+    self
+  }
+
+  /// Makes the streams yield a [`ClipboardSnapshot`](crate::ClipboardSnapshot) with every available representation of the content,
+  /// instead of only picking the one with the highest priority.
+  ///
+  /// Representations that are empty or beyond the allowed size are left out of the snapshot.
+  pub fn snapshots(mut self) -> Self {
+    self.snapshots = true;
+    self
+  }
+
+  /// Keeps images in the format in which they were found on the clipboard (PNG, JPEG, GIF, WebP, TIFF or BMP),
+  /// instead of converting them with the [`image_encoding`](Self::image_encoding).
+  ///
+  /// Images in other formats, such as the bitmaps used on Windows, are still converted.
+  pub fn keep_image_encoding(mut self) -> Self {
+    self.keep_image_encoding = true;
+    self
+  }
+
+  /// Sets the encoding used for the images that have to be converted. If unset, it defaults to PNG.
+  ///
+  /// Images that already have this encoding are kept as they are.
+  pub fn image_encoding(mut self, encoding: ImageEncoding) -> Self {
+    self.image_encoding = encoding;
+    self
+  }
+
+  /// Creates a PNG [`thumbnail`](crate::ClipboardImage::thumbnail) for each image, which fits within a square of the given size in pixels.
+  ///
+  /// Images that are already smaller than that are not scaled up.
+  pub fn image_thumbnails(mut self, max_edge: u32) -> Self {
+    self.thumbnail_size = Some(max_edge.max(1));
+    self
+  }
+
+  /// Sets how text that is not valid in its encoding is handled. If unset, it defaults to [`Lossy`](TextDecoding::Lossy).
+  ///
+  /// Text is decoded from UTF-8 and UTF-16, as well as the legacy `STRING` (ISO 8859-1) and `COMPOUND_TEXT` encodings on X11.
+  /// When several representations of the text are available, the first valid one is used before this policy applies.
+  pub fn text_decoding(mut self, decoding: TextDecoding) -> Self {
+    self.text_decoding = decoding;
+    self
+  }
+
+  /// Derives the plain text from the Rich Text Format representation, when the clipboard only offers the latter.
+  ///
+  /// The conversion keeps the text of the document and drops its formatting.
+  pub fn rtf_to_text(mut self) -> Self {
+    self.rtf_to_text = true;
+    self
+  }
+
+  /// Classifies the text of each item, to find whether it is a URL, an e-mail address, a path, a color code, JSON,
+  /// a number, a phone number or source code. The result is set as the [`classification`](crate::ClipboardEvent::classification) of the event.
+  ///
+  /// Plain text is preferred over HTML, which is classified by its text. Lazy content is not classified, since it is only read when fetched.
+  pub fn classify(mut self) -> Self {
+    self.classifiers.builtin = true;
+    self
+  }
+
+  /// Adds a custom [`Classifier`]. Custom classifiers run in the order they are added, before the built-in ones
+  /// (if [`classify`](Self::classify) is used), and the first match is used.
+  pub fn classifier<C: Classifier>(mut self, classifier: C) -> Self {
+    self.classifiers.custom.push(Arc::new(classifier));
+    self
+  }
+
+  /// Looks for secrets in the text of each item (plain text, HTML and RTF): API keys and access tokens with a well-known prefix,
+  /// private keys, JSON Web Tokens and payment card numbers.
+  ///
+  /// When a secret is found, the [`secret_action`](Self::secret_action) is applied before the item reaches the streams,
+  /// and the kinds of the secrets are set as the [`secrets`](crate::ClipboardEvent::secrets) of the event.
+  /// Lazy content is only scanned when it is [fetched](crate::LazyContent::fetch), so the kinds of its secrets are not part of the event.
+  pub fn detect_secrets(mut self) -> Self {
+    self.secrets.builtin = true;
+    self
+  }
+
+  /// Adds a custom [`SecretRule`], which runs along with the built-in ones (if [`detect_secrets`](Self::detect_secrets) is used).
+  pub fn secret_rule<R: SecretRule>(mut self, rule: R) -> Self {
+    self.secrets.custom.push(Arc::new(rule));
+    self
+  }
+
+  /// Sets what to do with the items in which a secret is found. If unset, they are redacted.
+  pub fn secret_action(mut self, action: SecretAction) -> Self {
+    self.secrets.action = action;
+    self
+  }
+
+  /// Makes the streams yield a [`LazyContent`](crate::LazyContent) for each change, which only lists the available formats.
+  ///
+  /// The content is only read from the clipboard when it is [`fetched`](crate::LazyContent::fetch), which avoids
+  /// copying and converting large items that are never used. It takes precedence over [`snapshots`](Self::snapshots).
+  pub fn lazy(mut self) -> Self {
+    self.lazy = true;
+    self
+  }
+
+  /// Makes the streams yield a [`Skipped`](crate::ClipboardContent::Skipped) event when the content of a change is not captured,
+  /// because it is empty or beyond the size limits, instead of ignoring the change.
+  ///
+  /// The [`SkippedContent`](crate::SkippedContent) tells the reason, along with the format and the size of the content when they are known.
+  pub fn report_skipped(mut self) -> Self {
+    self.report_skipped = true;
+    self
+  }
+
+  /// Sets how to handle the content that password managers mark as secret. If unset, such changes are dropped.
+  ///
+  /// See [`ConcealedPolicy`] for the markers that are detected.
+  pub fn concealed(mut self, policy: ConcealedPolicy) -> Self {
+    self.concealed = policy;
+    self
+  }
+
+  /// Sets the kinds of clipboard to monitor. If unset, only the regular clipboard is monitored.
+  ///
+  /// Each item received by a [`ClipboardStream`] is tagged with the [`ClipboardKind`] it comes from.
+  ///
+  /// The [`Primary`](ClipboardKind::Primary) selection is only available on Linux (or with a custom [`backend`](Self::backend)), so requesting it on other platforms will make [`spawn`](Self::spawn) fail.
+  pub fn kinds<I>(mut self, kinds: I) -> Self
+  where
+    I: IntoIterator<Item = ClipboardKind>,
+  {
+    self.kinds.clear();
+
+    for kind in kinds {
+      if !self.kinds.contains(&kind) {
+        self.kinds.push(kind);
+      }
+    }
+
+    self
+  }
+
+  /// Discards the changes made by the listener itself through [`set`](ClipboardEventListener::set), instead of delivering them
+  /// with the [`own_write`](crate::ClipboardEvent::own_write) flag.
+  pub fn ignore_own_writes(mut self) -> Self {
+    self.ignore_own_writes = true;
+    self
+  }
+
+  /// Uses a custom [`ClipboardBackend`] instead of the system clipboard.
+  ///
+  /// The other options of the builder still apply to the items sent by the backend.
+  pub fn backend<B: ClipboardBackend>(mut self, backend: B) -> Self {
+    self.custom_backend = Some(Box::new(backend));
+    self
+  }
+
   /// Spawns the [`ClipboardEventListener`].
-  pub fn spawn(self) -> Result<ClipboardEventListener, ClipboardError> {
+  ///
+  /// On Linux, the best available [`Backend`] is picked automatically. If none of them can be started,
+  /// a [`NoBackendAvailable`](ClipboardError::NoBackendAvailable) error is returned with the reason for each failure.
+  pub fn spawn(mut self) -> Result<ClipboardEventListener, ClipboardError> {
+    if self.kinds.is_empty() {
+      return Err(ClipboardError::InitializationError(
+        "no clipboard kind was selected for monitoring".to_string(),
+      ));
+    }
+
+    if FormatKind::DEFAULT_PRIORITY
+      .iter()
+      .all(|kind| self.disabled_formats.contains(kind))
+    {
+      return Err(ClipboardError::InitializationError(
+        "every clipboard format was disabled".to_string(),
+      ));
+    }
+
     let body_senders = Arc::new(BodySenders::new());
 
-    let driver = Driver::new(
-      body_senders.clone(),
-      self.interval,
-      self.custom_formats,
-      self.max_image_bytes,
-      self.max_bytes,
-    )?;
+    let (requests, requests_rx) = std_mpsc::channel();
+
+    let extractor = Extractor::new(&self, requests.clone());
+
+    let sink = ClipboardSink::new(body_senders.clone(), requests_rx, extractor.clone(), &self);
+
+    if let Some(backend) = self.custom_backend.take() {
+      return Ok(ClipboardEventListener {
+        backend: Backend::Custom,
+        driver: Some(Driver::custom(backend, sink)),
+        requests,
+        body_senders,
+        id: AtomicUsize::new(0),
+      });
+    }
+
+    #[cfg(not(all(unix, not(target_os = "macos"))))]
+    if self.kinds.contains(&ClipboardKind::Primary) {
+      return Err(ClipboardError::InitializationError(
+        "the primary selection is only available on Linux".to_string(),
+      ));
+    }
+
+    let driver = Driver::new(sink, extractor)?;
+
     Ok(ClipboardEventListener {
+      backend: driver.backend,
       driver: Some(driver),
+      requests,
       body_senders,
       id: AtomicUsize::new(0),
     })
@@ -99,6 +392,24 @@ impl ClipboardEventListener {
       custom_formats: vec![],
       max_image_bytes: None,
       max_bytes: None,
+      kinds: vec![ClipboardKind::Clipboard],
+      custom_backend: None,
+      ignore_own_writes: false,
+      snapshots: false,
+      priority: FormatKind::DEFAULT_PRIORITY.to_vec(),
+      disabled_formats: vec![],
+      ignored_apps: vec![],
+      lazy: false,
+      report_skipped: false,
+      concealed: ConcealedPolicy::Drop,
+      rtf_to_text: false,
+      keep_image_encoding: false,
+      image_encoding: ImageEncoding::Png,
+      thumbnail_size: None,
+      text_decoding: TextDecoding::Lossy,
+      file_metadata: None,
+      classifiers: Classifiers::default(),
+      secrets: SecretDetector::default(),
     }
   }
 
@@ -109,7 +420,44 @@ impl ClipboardEventListener {
     Self::builder().spawn()
   }
 
-  /// Creates a [`ClipboardStream`] for receiving clipboard change items as [`Body`].
+  /// Returns the [`Backend`] used to monitor the clipboard.
+  pub fn backend(&self) -> Backend {
+    self.backend
+  }
+
+  /// Sets the content of the regular clipboard.
+  ///
+  /// The change is delivered to the streams like any other, but with the [`own_write`](crate::ClipboardEvent::own_write) flag set,
+  /// unless [`ignore_own_writes`](ClipboardEventListenerBuilder::ignore_own_writes) was used.
+  ///
+  /// Writing to the clipboard is only supported by the Linux backends, and it returns [`ClipboardError::Unsupported`] with the others.
+  /// On X11, content larger than the maximum request size of the server (usually 16 MiB) is refused with [`ClipboardError::WriteError`].
+  pub async fn set(&self, body: Body) -> Result<(), ClipboardError> {
+    self.set_kind(ClipboardKind::Clipboard, body).await
+  }
+
+  /// Sets the content of the given kind of clipboard. See [`set`](Self::set) for more information.
+  pub async fn set_kind(&self, kind: ClipboardKind, body: Body) -> Result<(), ClipboardError> {
+    if !matches!(
+      self.backend,
+      Backend::WaylandDataControl | Backend::X11XFixes | Backend::X11Polling
+    ) {
+      return Err(ClipboardError::Unsupported(self.backend));
+    }
+
+    let stopped = || ClipboardError::WriteError("the clipboard monitor is not running".to_string());
+
+    let (reply, reply_rx) = oneshot::channel();
+
+    self
+      .requests
+      .send(Request::Set { kind, body, reply })
+      .map_err(|_| stopped())?;
+
+    reply_rx.await.map_err(|_| stopped())?
+  }
+
+  /// Creates a [`ClipboardStream`] for receiving clipboard change items as [`ClipboardEvent`].
   ///
   /// # Buffer size
   /// This method takes a buffer size. Items are buffered when not received immediately.
@@ -117,17 +465,17 @@ impl ClipboardEventListener {
   /// number of internal senders used by the library.
   ///
   /// # Example
-  /// ```
-  /// # use clipboard_stream::{Body, ClipboardEventListener, ClipboardStream};
+  /// ```no_run
+  /// # use clipboard_watcher::{Body, ClipboardEventListener, ClipboardStream};
   /// # #[tokio::main]
   /// # async fn main() {
-  ///     let mut event_listener = ClipboardEventListener::spawn();
+  ///     let mut event_listener = ClipboardEventListener::spawn().unwrap();
   ///
   ///     let buf_size = 32;
   ///     let stream = event_listener.new_stream(buf_size);
   /// # }
   /// ```
-  /// [`Body`]: crate::Body
+  /// [`ClipboardEvent`]: crate::ClipboardEvent
   pub fn new_stream(&mut self, buffer: usize) -> ClipboardStream {
     let (tx, rx) = mpsc::channel(buffer);
     let id = StreamId(self.id.fetch_add(1, Ordering::Relaxed));
@@ -0,0 +1,723 @@
+//! Content extraction shared by every backend, which only need to read each kind of content.
+
+use std::{
+  path::PathBuf,
+  sync::{Arc, mpsc::Sender},
+};
+
+use log::{debug, error};
+use url::Url;
+
+use crate::{
+  body::*,
+  error::{ClipboardError, ExtractionError},
+  event::{ClipboardContent, ClipboardKind, ConcealedPolicy, SkipReason, SkippedContent},
+  event_listener::ClipboardEventListenerBuilder,
+  files::file_entries,
+  image::*,
+  lazy::{AvailableFormat, ChangeId, ChangeTracker, LazyContent},
+  rtf::rtf_to_text,
+  sink::{BodyReader, Request},
+  text::TextDecoding,
+};
+
+/// Whether a format is present on the clipboard, as checked without reading the content.
+pub(crate) enum Probe {
+  Missing,
+  /// The size is only known if it can be found without reading the content.
+  Available {
+    size: Option<usize>,
+  },
+}
+
+/// A clipboard that can be read one kind of content at a time.
+///
+/// Every method returns `Ok(None)` if the content is not available, and an [`ExtractionError`] if
+/// the content is available but cannot be used.
+pub(crate) trait FormatReader {
+  /// Reads the content with the given custom format.
+  fn custom_format(
+    &mut self,
+    name: &str,
+    max_size: Option<usize>,
+  ) -> Result<Option<Vec<u8>>, ExtractionError>;
+
+  /// Reads an image, in the format with which it was found.
+  fn image(&mut self, max_size: Option<usize>) -> Result<Option<SourceImage>, ExtractionError>;
+
+  /// Reads a list of local files.
+  fn files_list(
+    &mut self,
+    max_size: Option<usize>,
+  ) -> Result<Option<Vec<PathBuf>>, ExtractionError>;
+
+  /// Reads a list of URIs that are not local files.
+  fn uri_list(&mut self, max_size: Option<usize>) -> Result<Option<Vec<Url>>, ExtractionError>;
+
+  /// Reads HTML content, with the source URL if the clipboard provides it.
+  fn html(&mut self) -> Result<Option<HtmlContent>, ExtractionError>;
+
+  /// Reads a document in the Rich Text Format.
+  fn rtf(&mut self) -> Result<Option<String>, ExtractionError>;
+
+  fn plain_text(&mut self) -> Result<Option<String>, ExtractionError>;
+
+  /// Checks whether the content is available with the given kind of format (and name, for custom formats).
+  ///
+  /// Kinds that share their formats (such as file lists and URI lists) may both be reported as
+  /// available, since telling them apart requires reading the content.
+  fn probe(&mut self, kind: FormatKind, name: Option<&str>) -> Result<Probe, ExtractionError>;
+
+  /// Checks whether the content was marked as secret by a password manager.
+  fn is_concealed(&mut self) -> bool;
+}
+
+/// Converts the errors that only concern a single representation into a missing representation,
+/// so that the other ones can still be extracted. The first skipped representation is kept in `skipped`.
+fn skip_unusable<T>(
+  format: FormatKind,
+  result: Result<Option<T>, ExtractionError>,
+  skipped: &mut Option<SkippedContent>,
+) -> Result<Option<T>, ExtractionError> {
+  let unusable = SkippedContent {
+    reason: SkipReason::Unusable,
+    format: Some(format),
+    size: None,
+  };
+
+  match result.map_err(|e| e.in_format(format)) {
+    Ok(content) => Ok(content),
+    Err(ExtractionError::Skipped(content)) => {
+      debug!("Found content that cannot be captured ({content:?}), skipping it...");
+      skipped.get_or_insert(content);
+      Ok(None)
+    }
+    Err(ExtractionError::ConversionError) => {
+      error!("{}", ClipboardError::ImageConversion);
+      skipped.get_or_insert(unusable);
+      Ok(None)
+    }
+    Err(ExtractionError::InvalidText) => {
+      error!("{}", ClipboardError::InvalidText);
+      skipped.get_or_insert(unusable);
+      Ok(None)
+    }
+    Err(e) => Err(e),
+  }
+}
+
+/// Picks the content of a [`FormatReader`] according to the options of the listener.
+#[derive(Debug, Clone)]
+pub(crate) struct Extractor {
+  custom_formats: Vec<Arc<str>>,
+  max_image_size: Option<usize>,
+  max_size: Option<usize>,
+  image_options: ImageOptions,
+  snapshots: bool,
+  priority: Vec<FormatKind>,
+  disabled_formats: Vec<FormatKind>,
+  rtf_to_text: bool,
+  text_decoding: TextDecoding,
+  file_metadata: Option<usize>,
+  report_skipped: bool,
+  concealed: ConcealedPolicy,
+  // Only set in lazy mode, to let the content be fetched later on
+  lazy: Option<Sender<Request>>,
+  changes: ChangeTracker,
+}
+
+impl Extractor {
+  pub(crate) fn new(options: &ClipboardEventListenerBuilder, requests: Sender<Request>) -> Self {
+    let max_image_size = if options.max_image_bytes.is_none() && options.max_bytes.is_some() {
+      debug!("Using global size limit for images...");
+      options.max_bytes
+    } else {
+      options.max_image_bytes
+    };
+
+    Extractor {
+      custom_formats: options.custom_formats.clone(),
+      max_image_size,
+      max_size: options.max_bytes,
+      image_options: ImageOptions {
+        keep_encoding: options.keep_image_encoding,
+        encoding: options.image_encoding,
+        thumbnail_size: options.thumbnail_size,
+      },
+      snapshots: options.snapshots,
+      // Disabled formats are left out entirely, so they are never read
+      priority: options
+        .priority
+        .iter()
+        .copied()
+        .filter(|kind| !options.disabled_formats.contains(kind))
+        .collect(),
+      disabled_formats: options.disabled_formats.clone(),
+      rtf_to_text: options.rtf_to_text,
+      text_decoding: options.text_decoding,
+      file_metadata: options.file_metadata,
+      report_skipped: options.report_skipped,
+      concealed: options.concealed,
+      lazy: options.lazy.then_some(requests),
+      changes: ChangeTracker::default(),
+    }
+  }
+
+  #[cfg(windows)]
+  pub(crate) fn custom_formats(&self) -> &[Arc<str>] {
+    &self.custom_formats
+  }
+
+  /// The policy used by the backends that have to decode text themselves.
+  #[cfg_attr(windows, allow(dead_code))]
+  pub(crate) fn text_decoding(&self) -> TextDecoding {
+    self.text_decoding
+  }
+
+  fn rank(&self, kind: FormatKind) -> usize {
+    self
+      .priority
+      .iter()
+      .position(|k| *k == kind)
+      .unwrap_or(usize::MAX)
+  }
+
+  fn is_enabled(&self, kind: FormatKind) -> bool {
+    self.priority.contains(&kind)
+  }
+
+  /// Checks whether the content is available in a disabled format, without reading it.
+  fn has_disabled_format(&self, reader: &mut impl FormatReader) -> bool {
+    let mut available = |kind: FormatKind, name: Option<&str>| {
+      matches!(reader.probe(kind, name), Ok(Probe::Available { .. }))
+    };
+
+    self.disabled_formats.iter().any(|kind| match kind {
+      FormatKind::Custom => self
+        .custom_formats
+        .iter()
+        .any(|name| available(*kind, Some(name))),
+      _ => available(*kind, None),
+    })
+  }
+
+  /// Reads the file list, unless it is disabled.
+  fn files_list(
+    &self,
+    reader: &mut impl FormatReader,
+  ) -> Result<Option<Vec<PathBuf>>, ExtractionError> {
+    if self.is_enabled(FormatKind::FileList) {
+      reader.files_list(self.max_size)
+    } else {
+      Ok(None)
+    }
+  }
+
+  /// Whether the plain text can be derived from the RTF document, which must not be read if disabled.
+  fn derives_text_from_rtf(&self) -> bool {
+    self.rtf_to_text && self.is_enabled(FormatKind::Rtf)
+  }
+
+  /// Reads the plain text, or derives it from the RTF document if the option is enabled.
+  fn plain_text(&self, reader: &mut impl FormatReader) -> Result<Option<String>, ExtractionError> {
+    match reader.plain_text()? {
+      Some(text) => Ok(Some(text)),
+      None if self.derives_text_from_rtf() => Ok(reader.rtf()?.map(|rtf| {
+        debug!("Converting RTF content to plain text...");
+        rtf_to_text(&rtf)
+      })),
+      None => Ok(None),
+    }
+  }
+
+  /// Reads an image and encodes it according to the options.
+  fn image(
+    &self,
+    reader: &mut impl FormatReader,
+  ) -> Result<Option<ClipboardImage>, ExtractionError> {
+    match reader.image(self.max_image_size)? {
+      Some(source) => process_image(source, &self.image_options)
+        .map(Some)
+        // We got the content but failed to convert it, trigger early exit
+        .ok_or(ExtractionError::ConversionError),
+      None => Ok(None),
+    }
+  }
+
+  /// Processes a single image file in a list as an image, if the file is readable and within the size limits.
+  fn image_from_files_list(&self, files_list: &[PathBuf]) -> Option<ClipboardImage> {
+    // We check if there is only one file in the list
+    if files_list.len() == 1
+      && let Some(path) = files_list.first()
+      // Then, if it's an image
+      && file_is_image(path)
+      // Then, if the size is within the allowed range
+      && self.max_image_size.is_none_or(|max| path.metadata().is_ok_and(|metadata| metadata.len() <= max as u64))
+      // Then, if the bytes are readable and the conversion is successful
+      && let Some(source) = read_image_file(path)
+      && let Some(image) = process_image(source, &self.image_options)
+    //
+    // Only if all of these are true, we save it as an image
+    {
+      debug!("Found file path with image format. Processing it as an image...");
+
+      Some(ClipboardImage {
+        path: Some(path.clone()),
+        ..image
+      })
+    } else {
+      None
+    }
+  }
+
+  /// Extracts the content with the highest priority.
+  fn extract_body(&self, reader: &mut impl FormatReader) -> Result<Option<Body>, ExtractionError> {
+    for kind in self.priority.iter() {
+      if let Some(body) = self
+        .extract_kind(*kind, reader)
+        .map_err(|e| e.in_format(*kind))?
+      {
+        return Ok(Some(body));
+      }
+    }
+
+    Ok(None)
+  }
+
+  /// Extracts the content with the given kind of format, if it is available.
+  fn extract_kind(
+    &self,
+    kind: FormatKind,
+    reader: &mut impl FormatReader,
+  ) -> Result<Option<Body>, ExtractionError> {
+    match kind {
+      FormatKind::Custom => {
+        for name in self.custom_formats.iter() {
+          if let Some(bytes) = reader.custom_format(name, self.max_size)? {
+            debug!("Found content with custom format `{name}`");
+
+            return Ok(Some(Body::Custom {
+              name: name.clone(),
+              data: bytes.into(),
+            }));
+          }
+        }
+      }
+      FormatKind::Image => {
+        if let Some(image) = self.image(reader)? {
+          // If there is only one path in the file list, which is sometimes emitted
+          // when copying an image, we assign it to the image.
+          // The path is optional, so failing to read it must not lose the image
+          let image_path = if let Some(mut files_list) = self.files_list(reader).ok().flatten()
+            && files_list.len() == 1
+          {
+            Some(files_list.remove(0))
+          } else {
+            None
+          };
+
+          return Ok(Some(Body::Image(ClipboardImage {
+            path: image_path,
+            ..image
+          })));
+        }
+      }
+      FormatKind::FileList => {
+        if let Some(files_list) = reader.files_list(self.max_size)? {
+          // A single image file is only processed as an image if images are preferred
+          let image = (self.rank(FormatKind::Image) < self.rank(FormatKind::FileList))
+            .then(|| self.image_from_files_list(&files_list))
+            .flatten();
+
+          return match image {
+            Some(image) => Ok(Some(Body::Image(image))),
+            None => Ok(Some(Body::FileList(file_entries(
+              files_list,
+              self.file_metadata,
+            )))),
+          };
+        }
+      }
+      FormatKind::UriList => {
+        if let Some(uris) = reader.uri_list(self.max_size)? {
+          return Ok(Some(Body::UriList(uris)));
+        }
+      }
+      FormatKind::Html => {
+        if let Some(html) = reader.html()? {
+          debug!("Extracted HTML content from clipboard");
+          return Ok(Some(Body::Html(html)));
+        }
+      }
+      FormatKind::Rtf => {
+        if let Some(rtf) = reader.rtf()? {
+          debug!("Extracted RTF content from clipboard");
+          return Ok(Some(Body::Rtf(rtf)));
+        }
+      }
+      FormatKind::PlainText => {
+        if let Some(plain) = self.plain_text(reader)? {
+          debug!("Extracted plain text from clipboard");
+          return Ok(Some(Body::PlainText(plain)));
+        }
+      }
+    }
+
+    Ok(None)
+  }
+
+  /// Extracts every available representation of the content, in order of priority.
+  fn extract_snapshot(
+    &self,
+    reader: &mut impl FormatReader,
+  ) -> Result<Option<ClipboardSnapshot>, ExtractionError> {
+    let mut representations = Vec::new();
+    // The first representation that was found but could not be used
+    let mut skipped = None;
+
+    // Needed by both the image and the file list representations
+    let files_list = skip_unusable(FormatKind::FileList, self.files_list(reader), &mut skipped)?;
+
+    for kind in self.priority.iter() {
+      match kind {
+        FormatKind::Custom => {
+          for name in self.custom_formats.iter() {
+            if let Some(bytes) = skip_unusable(
+              FormatKind::Custom,
+              reader.custom_format(name, self.max_size),
+              &mut skipped,
+            )? {
+              representations.push(Body::Custom {
+                name: name.clone(),
+                data: bytes.into(),
+              });
+            }
+          }
+        }
+        FormatKind::Image => {
+          let image = match skip_unusable(FormatKind::Image, self.image(reader), &mut skipped)? {
+            Some(image) => Some(ClipboardImage {
+              path: files_list
+                .as_ref()
+                .filter(|files_list| files_list.len() == 1)
+                .and_then(|files_list| files_list.first().cloned()),
+              ..image
+            }),
+            None => files_list
+              .as_ref()
+              .and_then(|files_list| self.image_from_files_list(files_list)),
+          };
+
+          representations.extend(image.map(Body::Image));
+        }
+        FormatKind::FileList => representations.extend(
+          files_list
+            .clone()
+            .map(|files_list| Body::FileList(file_entries(files_list, self.file_metadata))),
+        ),
+        FormatKind::UriList => representations.extend(
+          skip_unusable(*kind, reader.uri_list(self.max_size), &mut skipped)?.map(Body::UriList),
+        ),
+        FormatKind::Html => {
+          representations.extend(skip_unusable(*kind, reader.html(), &mut skipped)?.map(Body::Html))
+        }
+        FormatKind::Rtf => {
+          representations.extend(skip_unusable(*kind, reader.rtf(), &mut skipped)?.map(Body::Rtf))
+        }
+        FormatKind::PlainText => representations.extend(
+          skip_unusable(*kind, self.plain_text(reader), &mut skipped)?.map(Body::PlainText),
+        ),
+      }
+    }
+
+    if !representations.is_empty() {
+      debug!(
+        "Extracted {} representations from clipboard",
+        representations.len()
+      );
+      Ok(Some(ClipboardSnapshot::new(representations)))
+    } else if let Some(skipped) = skipped {
+      Err(ExtractionError::Skipped(skipped))
+    } else {
+      Ok(None)
+    }
+  }
+
+  /// Lists the formats in which the content is available, in order of priority, without reading them.
+  fn extract_formats(
+    &self,
+    reader: &mut impl FormatReader,
+  ) -> Result<Vec<AvailableFormat>, ExtractionError> {
+    let mut formats = Vec::new();
+    // The first format that was beyond the allowed size
+    let mut skipped = None;
+
+    let mut probe = |kind: FormatKind, name: Option<&Arc<str>>| -> Result<(), ExtractionError> {
+      let max_size = match kind {
+        FormatKind::Image => self.max_image_size,
+        FormatKind::Custom | FormatKind::FileList | FormatKind::UriList => self.max_size,
+        FormatKind::Html | FormatKind::Rtf | FormatKind::PlainText => None,
+      };
+
+      let found = match reader.probe(kind, name.map(AsRef::as_ref))? {
+        // The plain text can be derived from the RTF document
+        Probe::Missing if kind == FormatKind::PlainText && self.derives_text_from_rtf() => {
+          match reader.probe(FormatKind::Rtf, None)? {
+            Probe::Available { .. } => Probe::Available { size: None },
+            Probe::Missing => Probe::Missing,
+          }
+        }
+        found => found,
+      };
+
+      match found {
+        Probe::Available { size }
+          if size.is_some_and(|size| max_size.is_some_and(|max| size > max)) =>
+        {
+          debug!("Found content beyond allowed size, skipping it...");
+          skipped.get_or_insert(SkippedContent {
+            reason: SkipReason::TooLarge,
+            format: Some(kind),
+            size,
+          });
+        }
+        Probe::Available { size } => formats.push(AvailableFormat {
+          kind,
+          name: name.cloned(),
+          size,
+        }),
+        Probe::Missing => {}
+      }
+
+      Ok(())
+    };
+
+    for kind in self.priority.iter() {
+      if *kind == FormatKind::Custom {
+        for name in self.custom_formats.iter() {
+          probe(*kind, Some(name))?;
+        }
+      } else {
+        probe(*kind, None)?;
+      }
+    }
+
+    match skipped {
+      Some(skipped) if formats.is_empty() => Err(ExtractionError::Skipped(skipped)),
+      _ => Ok(formats),
+    }
+  }
+
+  fn extract_content(
+    &mut self,
+    kind: ClipboardKind,
+    reader: &mut impl FormatReader,
+  ) -> Result<Option<ClipboardContent>, ExtractionError> {
+    if let Some(requests) = &self.lazy {
+      let formats = self.extract_formats(reader)?;
+
+      if formats.is_empty() {
+        return Ok(None);
+      }
+
+      debug!("Found {} formats on the clipboard", formats.len());
+
+      let change = self.changes.record(kind);
+
+      Ok(Some(ClipboardContent::Lazy(Arc::new(LazyContent::new(
+        change,
+        formats,
+        requests.clone(),
+      )))))
+    } else {
+      self.extract_ready(reader)
+    }
+  }
+
+  /// Extracts the content at once, as a snapshot or a single body.
+  fn extract_ready(
+    &self,
+    reader: &mut impl FormatReader,
+  ) -> Result<Option<ClipboardContent>, ExtractionError> {
+    if self.snapshots {
+      Ok(
+        self
+          .extract_snapshot(reader)?
+          .map(|snapshot| ClipboardContent::Snapshot(Arc::new(snapshot))),
+      )
+    } else {
+      Ok(
+        self
+          .extract_body(reader)?
+          .map(|body| ClipboardContent::Body(Arc::new(body))),
+      )
+    }
+  }
+
+  pub(crate) fn get_clipboard_content(
+    &mut self,
+    kind: ClipboardKind,
+    reader: &mut impl FormatReader,
+  ) -> Result<Option<ClipboardContent>, ClipboardError> {
+    // Whatever happens to this change, the previous content is gone, so it can no longer be fetched
+    self.changes.invalidate(kind);
+
+    // Secrets are checked before reading anything
+    if self.concealed != ConcealedPolicy::Capture && reader.is_concealed() {
+      debug!("Found content marked as secret, skipping it...");
+
+      return Ok(
+        (self.concealed == ConcealedPolicy::Redact).then_some(ClipboardContent::Concealed),
+      );
+    }
+
+    let result = self.extract_content(kind, reader);
+    self.handle_extraction(result, reader)
+  }
+
+  /// Reads an item sent by a custom backend, which is complete already, so it is never fetched in lazy mode.
+  pub(crate) fn read_body(&self, body: Body) -> Result<Option<ClipboardContent>, ClipboardError> {
+    let mut reader = BodyReader {
+      body,
+      concealed: false,
+    };
+
+    let result = match self.lazy {
+      Some(_) => self
+        .extract_body(&mut reader)
+        .map(|body| body.map(|body| self.ready_content(body))),
+      None => self.extract_ready(&mut reader),
+    };
+
+    self.handle_extraction(result, &mut reader)
+  }
+
+  /// Wraps a single item in the kind of content produced by the listener.
+  pub(crate) fn ready_content(&self, body: Body) -> ClipboardContent {
+    if self.lazy.is_some() {
+      ClipboardContent::Lazy(Arc::new(LazyContent::ready(body)))
+    } else if self.snapshots {
+      ClipboardContent::Snapshot(Arc::new(ClipboardSnapshot::new(vec![body])))
+    } else {
+      ClipboardContent::Body(Arc::new(body))
+    }
+  }
+
+  /// Turns the outcome of an extraction into the content to report, if any.
+  fn handle_extraction(
+    &self,
+    result: Result<Option<ClipboardContent>, ExtractionError>,
+    reader: &mut impl FormatReader,
+  ) -> Result<Option<ClipboardContent>, ClipboardError> {
+    let skipped = match result {
+      // Found content
+      Ok(Some(content)) => return Ok(Some(content)),
+      // Non-fatal errors, the content is skipped
+      Err(ExtractionError::Skipped(skipped)) => skipped,
+      Err(ExtractionError::EmptyContent) => SkippedContent {
+        reason: SkipReason::Empty,
+        format: None,
+        size: None,
+      },
+      Err(ExtractionError::SizeTooLarge(size)) => SkippedContent {
+        reason: SkipReason::TooLarge,
+        format: None,
+        size,
+      },
+
+      // Actual errors, we send them
+      Err(ExtractionError::ConversionError) => return Err(ClipboardError::ImageConversion),
+      Err(ExtractionError::InvalidText) => return Err(ClipboardError::InvalidText),
+      Err(ExtractionError::ReadError(e)) => return Err(ClipboardError::ReadError(e)),
+      // The content only has formats that were disabled on purpose
+      Ok(None) if self.has_disabled_format(reader) => {
+        debug!("Found content with a disabled format, skipping it...");
+        return Ok(None);
+      }
+      // There was content but we could not read it
+      Ok(None) => return Err(ClipboardError::NoMatchingFormat),
+    };
+
+    debug!("Found content that cannot be captured ({skipped:?}), skipping it...");
+
+    // The change is only reported if the option is enabled
+    Ok(
+      self
+        .report_skipped
+        .then_some(ClipboardContent::Skipped(skipped)),
+    )
+  }
+
+  /// Forgets the latest change of a kind of clipboard, so that its lazy content can no longer be fetched.
+  #[cfg_attr(not(all(unix, not(target_os = "macos"))), allow(dead_code))]
+  pub(crate) fn invalidate(&mut self, kind: ClipboardKind) {
+    self.changes.invalidate(kind);
+  }
+
+  /// Reads the content of a lazy change in the given format.
+  pub(crate) fn fetch(
+    &self,
+    change: &ChangeId,
+    format: &AvailableFormat,
+    reader: &mut impl FormatReader,
+  ) -> Result<Body, ClipboardError> {
+    if !self.changes.is_current(change) {
+      return Err(ClipboardError::ContentChanged);
+    }
+
+    let body = match format.kind {
+      FormatKind::Custom => {
+        let name = format
+          .name
+          .clone()
+          .ok_or(ClipboardError::NoMatchingFormat)?;
+
+        reader.custom_format(&name, self.max_size).map(|data| {
+          data.map(|data| Body::Custom {
+            name,
+            data: data.into(),
+          })
+        })
+      }
+      FormatKind::Image => self.image(reader).map(|image| {
+        image.map(|image| {
+          let path = self
+            .files_list(reader)
+            .ok()
+            .flatten()
+            .filter(|files_list| files_list.len() == 1)
+            .and_then(|mut files_list| files_list.pop());
+
+          Body::Image(ClipboardImage { path, ..image })
+        })
+      }),
+      FormatKind::FileList => reader.files_list(self.max_size).map(|files_list| {
+        files_list.map(|files_list| Body::FileList(file_entries(files_list, self.file_metadata)))
+      }),
+      FormatKind::UriList => reader
+        .uri_list(self.max_size)
+        .map(|uris| uris.map(Body::UriList)),
+      FormatKind::Html => reader.html().map(|html| html.map(Body::Html)),
+      FormatKind::Rtf => reader.rtf().map(|rtf| rtf.map(Body::Rtf)),
+      FormatKind::PlainText => self
+        .plain_text(reader)
+        .map(|text| text.map(Body::PlainText)),
+    };
+
+    match body {
+      Ok(Some(body)) => Ok(body),
+      // Both kinds are listed when their shared formats are present, but only one may be held
+      Ok(None) if matches!(format.kind, FormatKind::FileList | FormatKind::UriList) => {
+        Err(ClipboardError::NoMatchingFormat)
+      }
+      // The format was there when the change was detected, so the content was replaced in the meantime
+      Ok(None) | Err(ExtractionError::EmptyContent) => Err(ClipboardError::ContentChanged),
+      Err(ExtractionError::SizeTooLarge(_) | ExtractionError::Skipped(_)) => Err(
+        ClipboardError::ReadError("the content is beyond the allowed size".to_string()),
+      ),
+      Err(ExtractionError::ConversionError) => Err(ClipboardError::ImageConversion),
+      Err(ExtractionError::InvalidText) => Err(ClipboardError::InvalidText),
+      Err(ExtractionError::ReadError(e)) => Err(ClipboardError::ReadError(e)),
+    }
+  }
+}
@@ -0,0 +1,389 @@
+//! Inspection of the files in a file list, to gather their metadata and sniff their MIME type.
+
+use std::{
+  fs::File,
+  io::{ErrorKind, Read},
+  path::{Path, PathBuf},
+};
+
+use log::debug;
+
+use crate::body::{FileEntry, FileMetadata};
+
+/// How many bytes are read from a file to find its MIME type.
+const SNIFF_LEN: usize = 512;
+
+/// The signatures found at the start of common file formats, with their MIME type.
+const SIGNATURES: [(&[u8], &str); 24] = [
+  (b"\x89PNG\r\n\x1a\n", "image/png"),
+  (b"\xff\xd8\xff", "image/jpeg"),
+  (b"GIF87a", "image/gif"),
+  (b"GIF89a", "image/gif"),
+  (b"II*\0", "image/tiff"),
+  (b"MM\0*", "image/tiff"),
+  (b"\0\0\x01\0", "image/x-icon"),
+  (b"%PDF-", "application/pdf"),
+  (b"PK\x03\x04", "application/zip"),
+  (b"\x1f\x8b", "application/gzip"),
+  (b"BZh", "application/x-bzip2"),
+  (b"\xfd7zXZ\0", "application/x-xz"),
+  (b"\x28\xb5\x2f\xfd", "application/zstd"),
+  (b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+  (b"Rar!\x1a\x07", "application/vnd.rar"),
+  (b"SQLite format 3\0", "application/vnd.sqlite3"),
+  (b"\x7fELF", "application/x-executable"),
+  (b"%!PS", "application/postscript"),
+  (b"ID3", "audio/mpeg"),
+  (b"fLaC", "audio/flac"),
+  (b"OggS", "audio/ogg"),
+  (b"\x1a\x45\xdf\xa3", "video/webm"),
+  (b"wOFF", "font/woff"),
+  (b"wOF2", "font/woff2"),
+];
+
+/// Finds the MIME type of a file from its first bytes.
+fn sniff_mime_type(bytes: &[u8]) -> Option<&'static str> {
+  if bytes.is_empty() {
+    return None;
+  }
+
+  if let Some((_, mime_type)) = SIGNATURES
+    .iter()
+    .find(|(signature, _)| bytes.starts_with(signature))
+  {
+    return Some(mime_type);
+  }
+
+  // RIFF is a container, whose format follows the size of the file
+  if bytes.starts_with(b"RIFF") && bytes.len() >= 12 {
+    return match &bytes[8..12] {
+      b"WEBP" => Some("image/webp"),
+      b"WAVE" => Some("audio/wav"),
+      b"AVI " => Some("video/x-msvideo"),
+      _ => Some("application/octet-stream"),
+    };
+  }
+
+  // The same goes for the ISO media formats, which are told apart by their brand
+  if bytes.len() >= 12 && &bytes[4..8] == b"ftyp" {
+    return match &bytes[8..12] {
+      b"avif" => Some("image/avif"),
+      b"heic" | b"heix" | b"mif1" => Some("image/heic"),
+      b"M4A " => Some("audio/mp4"),
+      b"qt  " => Some("video/quicktime"),
+      _ => Some("video/mp4"),
+    };
+  }
+
+  // Text has no signature, so it is recognized by being valid UTF-8 without control characters.
+  // The last character can be invalid, since it could have been cut.
+  let is_text = match std::str::from_utf8(bytes) {
+    Ok(text) => Some(text),
+    Err(e) if e.error_len().is_none() => std::str::from_utf8(&bytes[..e.valid_up_to()]).ok(),
+    Err(_) => None,
+  }
+  .is_some_and(|text| {
+    !text
+      .chars()
+      .any(|c| c.is_control() && !c.is_ascii_whitespace())
+  });
+
+  if !is_text {
+    return Some("application/octet-stream");
+  }
+
+  let start = String::from_utf8_lossy(bytes)
+    .trim_start()
+    .to_ascii_lowercase();
+
+  if start.starts_with("<!doctype html") || start.starts_with("<html") {
+    Some("text/html")
+  } else if start.starts_with("<svg") || (start.starts_with("<?xml") && start.contains("<svg")) {
+    Some("image/svg+xml")
+  } else if start.starts_with("<?xml") {
+    Some("application/xml")
+  } else {
+    Some("text/plain")
+  }
+}
+
+/// Reads the first bytes of a file.
+fn read_start(path: &Path) -> Option<Vec<u8>> {
+  let mut bytes = Vec::with_capacity(SNIFF_LEN);
+
+  File::open(path)
+    .and_then(|file| file.take(SNIFF_LEN as u64).read_to_end(&mut bytes))
+    .inspect_err(|e| debug!("Failed to read file `{}`: {e}", path.display()))
+    .ok()?;
+
+  Some(bytes)
+}
+
+fn inspect_file(path: &Path) -> FileMetadata {
+  let metadata = match path.metadata() {
+    Ok(metadata) => metadata,
+    Err(e) => {
+      debug!("Failed to inspect file `{}`: {e}", path.display());
+
+      return FileMetadata {
+        // The file may still exist if it could not be inspected, such as without the permission to do so
+        exists: !matches!(e.kind(), ErrorKind::NotFound | ErrorKind::NotADirectory),
+        is_dir: false,
+        size: None,
+        modified: None,
+        mime_type: None,
+      };
+    }
+  };
+
+  // Reading from special files (such as pipes or devices) could block forever
+  let is_file = metadata.is_file();
+
+  FileMetadata {
+    exists: true,
+    is_dir: metadata.is_dir(),
+    size: is_file.then_some(metadata.len()),
+    modified: metadata.modified().ok(),
+    mime_type: is_file
+      .then(|| read_start(path))
+      .flatten()
+      .and_then(|bytes| sniff_mime_type(&bytes))
+      .map(str::to_string),
+  }
+}
+
+/// Turns the paths of a file list into entries, inspecting the first `max_entries` of them if it is set.
+pub(crate) fn file_entries(paths: Vec<PathBuf>, max_entries: Option<usize>) -> Vec<FileEntry> {
+  paths
+    .into_iter()
+    .enumerate()
+    .map(|(i, path)| FileEntry {
+      metadata: max_entries
+        .is_some_and(|max| i < max)
+        .then(|| inspect_file(&path)),
+      path,
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{fs, process};
+
+  use super::*;
+
+  #[test]
+  fn signatures() {
+    assert_eq!(
+      sniff_mime_type(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"),
+      Some("image/png")
+    );
+    assert_eq!(sniff_mime_type(b"\xff\xd8\xff\xe0"), Some("image/jpeg"));
+    assert_eq!(sniff_mime_type(b"GIF89a"), Some("image/gif"));
+    assert_eq!(sniff_mime_type(b"%PDF-1.7\n"), Some("application/pdf"));
+    assert_eq!(
+      sniff_mime_type(b"PK\x03\x04\x14\0"),
+      Some("application/zip")
+    );
+    assert_eq!(
+      sniff_mime_type(b"\x7fELF\x02\x01\x01"),
+      Some("application/x-executable")
+    );
+  }
+
+  #[test]
+  fn containers() {
+    assert_eq!(sniff_mime_type(b"RIFF\0\0\0\0WEBPVP8 "), Some("image/webp"));
+    assert_eq!(sniff_mime_type(b"RIFF\0\0\0\0WAVEfmt "), Some("audio/wav"));
+    assert_eq!(
+      sniff_mime_type(b"RIFF\0\0\0\0????"),
+      Some("application/octet-stream")
+    );
+    assert_eq!(sniff_mime_type(b"\0\0\0\x1cftypavif"), Some("image/avif"));
+    assert_eq!(sniff_mime_type(b"\0\0\0\x18ftypheic"), Some("image/heic"));
+    assert_eq!(sniff_mime_type(b"\0\0\0\x20ftypisom"), Some("video/mp4"));
+  }
+
+  #[test]
+  fn truncated_containers() {
+    // Too short to hold the format, and not text either
+    assert_eq!(
+      sniff_mime_type(b"RIFF\0\0\0\0"),
+      Some("application/octet-stream")
+    );
+    assert_eq!(sniff_mime_type(b"RIFF"), Some("text/plain"));
+  }
+
+  #[test]
+  fn markup() {
+    assert_eq!(
+      sniff_mime_type(b"  \n<!DOCTYPE html><html></html>"),
+      Some("text/html")
+    );
+    assert_eq!(sniff_mime_type(b"<HTML><body>"), Some("text/html"));
+    assert_eq!(
+      sniff_mime_type(b"<svg xmlns=\"http://www.w3.org/2000/svg\"/>"),
+      Some("image/svg+xml")
+    );
+    assert_eq!(
+      sniff_mime_type(b"<?xml version=\"1.0\"?>\n<svg/>"),
+      Some("image/svg+xml")
+    );
+    assert_eq!(
+      sniff_mime_type(b"<?xml version=\"1.0\"?>\n<feed/>"),
+      Some("application/xml")
+    );
+  }
+
+  #[test]
+  fn text() {
+    assert_eq!(sniff_mime_type(b"Hello,\r\n\tworld!"), Some("text/plain"));
+    assert_eq!(sniff_mime_type("héllo 🎉".as_bytes()), Some("text/plain"));
+    // A character cut at the end of what was read
+    assert_eq!(
+      sniff_mime_type(&"héllo 🎉".as_bytes()[..9]),
+      Some("text/plain")
+    );
+  }
+
+  #[test]
+  fn binary() {
+    assert_eq!(
+      sniff_mime_type(b"text\0with a null"),
+      Some("application/octet-stream")
+    );
+    assert_eq!(
+      sniff_mime_type(b"\xff\xfe\xfd invalid"),
+      Some("application/octet-stream")
+    );
+    // An invalid sequence that is not at the end
+    assert_eq!(
+      sniff_mime_type(b"caf\xe9 au lait"),
+      Some("application/octet-stream")
+    );
+  }
+
+  #[test]
+  fn empty() {
+    assert_eq!(sniff_mime_type(b""), None);
+  }
+
+  /// A directory with some files, which is removed when dropped.
+  struct TempDir(PathBuf);
+
+  impl TempDir {
+    fn new(name: &str) -> Self {
+      let path = std::env::temp_dir().join(format!("clipboard-watcher-{name}-{}", process::id()));
+      fs::create_dir_all(&path).unwrap();
+      TempDir(path)
+    }
+
+    fn file(&self, name: &str, content: &[u8]) -> PathBuf {
+      let path = self.0.join(name);
+      fs::write(&path, content).unwrap();
+      path
+    }
+  }
+
+  impl Drop for TempDir {
+    fn drop(&mut self) {
+      let _ = fs::remove_dir_all(&self.0);
+    }
+  }
+
+  #[test]
+  fn entries_metadata() {
+    let dir = TempDir::new("entries");
+    let text = dir.file("notes", b"some notes");
+    let empty = dir.file("empty", b"");
+    let missing = dir.0.join("missing");
+
+    let entries = file_entries(
+      vec![text.clone(), empty.clone(), dir.0.clone(), missing.clone()],
+      None,
+    );
+    assert!(entries.iter().all(|entry| entry.metadata.is_none()));
+
+    let entries = file_entries(vec![text, empty, dir.0.clone(), missing], Some(10));
+    let metadata: Vec<&FileMetadata> = entries
+      .iter()
+      .map(|entry| entry.metadata.as_ref().unwrap())
+      .collect();
+
+    assert!(metadata[0].exists && !metadata[0].is_dir);
+    assert_eq!(metadata[0].size, Some(10));
+    assert!(metadata[0].modified.is_some());
+    assert_eq!(metadata[0].mime_type.as_deref(), Some("text/plain"));
+
+    assert_eq!(metadata[1].size, Some(0));
+    assert_eq!(metadata[1].mime_type, None);
+
+    assert!(metadata[2].exists && metadata[2].is_dir);
+    assert_eq!(metadata[2].size, None);
+    assert_eq!(metadata[2].mime_type, None);
+
+    assert!(!metadata[3].exists);
+    assert_eq!(metadata[3].modified, None);
+  }
+
+  #[test]
+  fn entries_below_a_file() {
+    let dir = TempDir::new("below");
+    let notes = dir.file("notes", b"some notes");
+
+    let entries = file_entries(vec![notes.join("child")], Some(1));
+    let metadata = entries[0].metadata.as_ref().unwrap();
+
+    assert!(!metadata.exists);
+    assert_eq!(metadata.size, None);
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn entries_special_files() {
+    let dir = TempDir::new("special");
+    let fifo = dir.0.join("fifo");
+
+    let status = process::Command::new("mkfifo").arg(&fifo).status().unwrap();
+    assert!(status.success());
+
+    // Opening the pipe would block until something writes to it
+    let entries = file_entries(vec![fifo, PathBuf::from("/dev/zero")], Some(2));
+
+    for entry in entries {
+      let metadata = entry.metadata.unwrap();
+
+      assert!(metadata.exists && !metadata.is_dir);
+      assert_eq!(metadata.size, None);
+      assert_eq!(metadata.mime_type, None);
+    }
+  }
+
+  #[test]
+  fn entries_limit() {
+    let dir = TempDir::new("limit");
+    let paths: Vec<PathBuf> = (0..3)
+      .map(|i| dir.file(&format!("{i}.png"), b"\x89PNG\r\n\x1a\n"))
+      .collect();
+
+    let entries = file_entries(paths.clone(), Some(2));
+
+    assert_eq!(
+      entries.iter().map(|entry| &entry.path).collect::<Vec<_>>(),
+      paths.iter().collect::<Vec<_>>()
+    );
+    assert_eq!(
+      entries[1]
+        .metadata
+        .as_ref()
+        .and_then(|metadata| metadata.mime_type.as_deref()),
+      Some("image/png")
+    );
+    assert_eq!(entries[2].metadata, None);
+    assert!(
+      file_entries(paths, Some(0))
+        .iter()
+        .all(|entry| entry.metadata.is_none())
+    );
+  }
+}
@@ -0,0 +1,249 @@
+//! Parsing of the HTML content offered by each platform, so that it has the same shape everywhere.
+
+use url::Url;
+
+use crate::body::HtmlContent;
+
+const START_FRAGMENT: &str = "<!--StartFragment-->";
+const END_FRAGMENT: &str = "<!--EndFragment-->";
+
+/// Finds the inner markup of the `body` element.
+fn body_range(lowercase: &str) -> Option<(usize, usize)> {
+  let open = lowercase.find("<body")?;
+  let start = open + lowercase[open..].find('>')? + 1;
+  let end = lowercase.rfind("</body>").filter(|end| *end >= start)?;
+
+  Some((start, end))
+}
+
+impl HtmlContent {
+  /// Splits the markup offered by the clipboard into the fragment and the document.
+  ///
+  /// The fragment is delimited by the `StartFragment` and `EndFragment` comments if present, or else by the `body` element.
+  /// Markup with neither of them is a fragment on its own.
+  pub(crate) fn from_markup(markup: String, source_url: Option<Url>) -> Self {
+    // Lowercasing ASCII characters keeps the same byte offsets
+    let lowercase = markup.to_ascii_lowercase();
+
+    let range = match (markup.find(START_FRAGMENT), markup.rfind(END_FRAGMENT)) {
+      (Some(start), Some(end)) if start + START_FRAGMENT.len() <= end => {
+        Some((start + START_FRAGMENT.len(), end))
+      }
+      _ => body_range(&lowercase),
+    };
+
+    match range {
+      Some((start, end)) => HtmlContent {
+        fragment: markup[start..end].to_string(),
+        document: Some(markup),
+        source_url,
+      },
+      None => {
+        // Chromium puts the charset of the fragment before it
+        let fragment = if lowercase.trim_start().starts_with("<meta")
+          && let Some(end) = lowercase.find('>')
+        {
+          markup[end + 1..].to_string()
+        } else {
+          markup
+        };
+
+        HtmlContent {
+          fragment,
+          document: None,
+          source_url,
+        }
+      }
+    }
+  }
+}
+
+/// Parses the `CF_HTML` format used on Windows, where a header gives the offsets of the document and the fragment.
+///
+/// The offsets count bytes, so the markup is only decoded after it is sliced.
+#[cfg_attr(not(windows), allow(dead_code))]
+pub(crate) fn parse_cf_html(data: &[u8]) -> Option<HtmlContent> {
+  // The data is null-terminated
+  let end = data
+    .iter()
+    .rposition(|b| *b != 0)
+    .map_or(0, |last| last + 1);
+  let data = &data[..end];
+
+  let mut start_html = None;
+  let mut end_html = None;
+  let mut start_fragment = None;
+  let mut end_fragment = None;
+  let mut source_url = None;
+
+  for line in data.split(|b| *b == b'\n') {
+    let line = String::from_utf8_lossy(line);
+
+    // The header ends where the markup starts
+    let Some((key, value)) = line
+      .trim_end_matches('\r')
+      .split_once(':')
+      .filter(|(key, _)| !key.starts_with('<'))
+    else {
+      break;
+    };
+
+    // Offsets are set to -1 when they are not used
+    let offset = || value.trim().parse::<usize>().ok();
+
+    match key {
+      "StartHTML" => start_html = offset(),
+      "EndHTML" => end_html = offset(),
+      "StartFragment" => start_fragment = offset(),
+      "EndFragment" => end_fragment = offset(),
+      "SourceURL" => source_url = Url::parse(value.trim()).ok(),
+      _ => {}
+    }
+  }
+
+  let slice = |start: Option<usize>, end: Option<usize>| {
+    data
+      .get(start?..end?)
+      .map(|markup| String::from_utf8_lossy(markup).into_owned())
+  };
+
+  let document = slice(start_html, end_html);
+
+  match slice(start_fragment, end_fragment) {
+    Some(fragment) => Some(HtmlContent {
+      fragment,
+      document,
+      source_url,
+    }),
+    // The fragment can still be found from its comments in the document
+    None => document.map(|document| HtmlContent::from_markup(document, source_url)),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn url(url: &str) -> Option<Url> {
+    Some(Url::parse(url).unwrap())
+  }
+
+  #[test]
+  fn fragment_from_comments() {
+    let markup =
+      "<html><body>before<!--StartFragment--><b>Hi</b><!--EndFragment-->after</body></html>";
+    let html = HtmlContent::from_markup(markup.to_string(), url("https://example.com"));
+
+    assert_eq!(html.fragment, "<b>Hi</b>");
+    assert_eq!(html.document.as_deref(), Some(markup));
+    assert_eq!(html.source_url, url("https://example.com"));
+  }
+
+  #[test]
+  fn fragment_from_body() {
+    let markup = "<HTML><BODY class=\"x\"><p>Hi</p></BODY></HTML>";
+    let html = HtmlContent::from_markup(markup.to_string(), None);
+
+    assert_eq!(html.fragment, "<p>Hi</p>");
+    assert_eq!(html.document.as_deref(), Some(markup));
+  }
+
+  #[test]
+  fn misplaced_comments_fall_back_to_body() {
+    let markup = "<body><!--EndFragment-->x<!--StartFragment--></body>";
+    let html = HtmlContent::from_markup(markup.to_string(), None);
+
+    assert_eq!(html.fragment, "<!--EndFragment-->x<!--StartFragment-->");
+  }
+
+  #[test]
+  fn bare_fragment() {
+    let html = HtmlContent::from_markup("<b>Hi</b>".to_string(), None);
+
+    assert_eq!(html.fragment, "<b>Hi</b>");
+    assert_eq!(html.document, None);
+    assert_eq!(html.markup(), "<b>Hi</b>");
+  }
+
+  #[test]
+  fn bare_fragment_without_its_charset() {
+    let html = HtmlContent::from_markup("<meta charset='utf-8'><b>Hi</b>".to_string(), None);
+
+    assert_eq!(html.fragment, "<b>Hi</b>");
+    assert_eq!(html.document, None);
+  }
+
+  /// Builds `CF_HTML` data, with the offsets of the markup between the markers.
+  fn cf_html(before: &[u8], fragment: &[u8], after: &[u8], source_url: Option<&str>) -> Vec<u8> {
+    let source_url = source_url
+      .map(|url| format!("SourceURL:{url}\r\n"))
+      .unwrap_or_default();
+    // The offsets have a fixed width, so the length of the header does not depend on them
+    let header = |offsets: [usize; 4]| {
+      format!(
+        "Version:0.9\r\nStartHTML:{:010}\r\nEndHTML:{:010}\r\nStartFragment:{:010}\r\nEndFragment:{:010}\r\n{source_url}",
+        offsets[0], offsets[1], offsets[2], offsets[3]
+      )
+    };
+
+    let start_html = header([0; 4]).len();
+    let start_fragment = start_html + before.len();
+    let end_fragment = start_fragment + fragment.len();
+    let end_html = end_fragment + after.len();
+
+    let mut data = header([start_html, end_html, start_fragment, end_fragment]).into_bytes();
+    data.extend_from_slice(before);
+    data.extend_from_slice(fragment);
+    data.extend_from_slice(after);
+    data.push(0);
+    data
+  }
+
+  #[test]
+  fn cf_html_offsets() {
+    let data = cf_html(
+      b"<html><body>",
+      "<b>caf\u{e9}</b>".as_bytes(),
+      b"</body></html>",
+      Some("https://example.com/page"),
+    );
+    let html = parse_cf_html(&data).unwrap();
+
+    assert_eq!(html.fragment, "<b>café</b>");
+    assert_eq!(
+      html.document.as_deref(),
+      Some("<html><body><b>café</b></body></html>")
+    );
+    assert_eq!(html.source_url, url("https://example.com/page"));
+  }
+
+  #[test]
+  fn cf_html_offsets_after_invalid_bytes() {
+    // Each invalid byte becomes a replacement character, which is longer, so the offsets must be applied before decoding
+    let data = cf_html(b"<body>\xff\xfe\xe9", b"<i>ok</i>", b"</body>", None);
+    let html = parse_cf_html(&data).unwrap();
+
+    assert_eq!(html.fragment, "<i>ok</i>");
+    assert_eq!(
+      html.document.as_deref(),
+      Some("<body>\u{fffd}\u{fffd}\u{fffd}<i>ok</i></body>")
+    );
+  }
+
+  #[test]
+  fn cf_html_without_fragment_offsets() {
+    let data = b"Version:0.9\r\nStartHTML:74\r\nEndHTML:140\r\nStartFragment:-1\r\nEndFragment:-1\r\n<html><body><!--StartFragment-->Hi<!--EndFragment--></body></html>";
+    let html = parse_cf_html(data).unwrap();
+
+    assert_eq!(html.fragment, "Hi");
+  }
+
+  #[test]
+  fn cf_html_with_invalid_header() {
+    assert_eq!(parse_cf_html(b"<html><body>Hi</body></html>"), None);
+    assert_eq!(
+      parse_cf_html(b"Version:0.9\r\nStartFragment:500\r\nEndFragment:600\r\n<b>Hi</b>"),
+      None
+    );
+  }
+}
@@ -1,32 +1,189 @@
 use std::{io::Cursor, path::Path};
 
-use image::ImageFormat;
+use bytes::Bytes;
+use image::{
+  ColorType, DynamicImage, ImageDecoder, ImageFormat, ImageReader,
+  codecs::{jpeg::JpegEncoder, webp::WebPEncoder},
+};
 use log::error;
 
+use crate::body::{ClipboardImage, ImageEncoding, MimeType};
+
+/// An image as it was read from the clipboard, before it is encoded according to the options of the listener.
+pub(crate) enum SourceImage {
+  /// An image in a known file format, which can be kept as it is.
+  Encoded { bytes: Vec<u8>, mime_type: MimeType },
+  /// A device-independent bitmap, which lacks the header of a BMP file.
+  #[cfg(windows)]
+  Dib(Vec<u8>),
+  /// An image sent by a custom backend, which is delivered as it is.
+  Processed(ClipboardImage),
+}
+
+impl MimeType {
+  fn image_format(self) -> ImageFormat {
+    match self {
+      MimeType::Png => ImageFormat::Png,
+      MimeType::Jpeg => ImageFormat::Jpeg,
+      MimeType::Gif => ImageFormat::Gif,
+      MimeType::WebP => ImageFormat::WebP,
+      MimeType::Tiff => ImageFormat::Tiff,
+      MimeType::Bmp => ImageFormat::Bmp,
+    }
+  }
+
+  fn from_image_format(format: ImageFormat) -> Option<Self> {
+    match format {
+      ImageFormat::Png => Some(MimeType::Png),
+      ImageFormat::Jpeg => Some(MimeType::Jpeg),
+      ImageFormat::Gif => Some(MimeType::Gif),
+      ImageFormat::WebP => Some(MimeType::WebP),
+      ImageFormat::Tiff => Some(MimeType::Tiff),
+      ImageFormat::Bmp => Some(MimeType::Bmp),
+      _ => None,
+    }
+  }
+}
+
 #[cfg(windows)]
-pub(crate) fn convert_dib_to_png(dib_bytes: &[u8]) -> Option<Vec<u8>> {
-  use image::{codecs::bmp::BmpDecoder, DynamicImage};
+fn decode_dib(dib_bytes: &[u8]) -> Option<DynamicImage> {
+  use image::codecs::bmp::BmpDecoder;
 
-  let cursor = Cursor::new(dib_bytes);
+  let decoder = BmpDecoder::new_without_file_header(Cursor::new(dib_bytes))
+    .inspect_err(|e| error!("Failed to load dib from clipboard: {e}"))
+    .ok()?;
 
-  let decoder = BmpDecoder::new_without_file_header(cursor).ok()?;
+  DynamicImage::from_decoder(decoder)
+    .inspect_err(|e| error!("Failed to load dib from clipboard: {e}"))
+    .ok()
+}
 
-  let dynamic_image = DynamicImage::from_decoder(decoder).ok()?;
+/// The options used to turn a [`SourceImage`] into a [`ClipboardImage`].
+#[derive(Debug, Clone)]
+pub(crate) struct ImageOptions {
+  /// Whether to keep the original bytes of images in a known format.
+  pub(crate) keep_encoding: bool,
+  /// The encoding used for the images that have to be converted.
+  pub(crate) encoding: ImageEncoding,
+  /// The maximum width and height of the thumbnails, if they are enabled.
+  pub(crate) thumbnail_size: Option<u32>,
+}
+
+fn load_image(bytes: &[u8], mime_type: MimeType) -> Option<DynamicImage> {
+  image::load_from_memory_with_format(bytes, mime_type.image_format())
+    .inspect_err(|e| error!("Failed to load image from clipboard: {e}"))
+    .ok()
+}
 
-  let mut png_buffer = Vec::new();
-  if dynamic_image
-    .write_to(&mut Cursor::new(&mut png_buffer), ImageFormat::Png)
-    .is_ok()
-  {
-    Some(png_buffer)
+/// Reads the dimensions and color type of an encoded image, without decoding all of it.
+fn read_metadata(bytes: &[u8], mime_type: MimeType) -> Option<(u32, u32, ColorType)> {
+  let decoder = ImageReader::with_format(Cursor::new(bytes), mime_type.image_format())
+    .into_decoder()
+    .inspect_err(|e| error!("Failed to read image metadata: {e}"))
+    .ok()?;
+
+  let (width, height) = decoder.dimensions();
+
+  Some((width, height, decoder.color_type()))
+}
+
+/// Encodes an image, and returns it as it was encoded (with the color type supported by the encoding).
+fn encode(image: DynamicImage, encoding: ImageEncoding) -> Option<(Bytes, DynamicImage)> {
+  let mut buffer = Vec::new();
+  let mut cursor = Cursor::new(&mut buffer);
+
+  // JPEG and WebP do not support every color type, so the image is converted first (which reuses its buffer if it already has the right one)
+  let image = match encoding {
+    ImageEncoding::Png => image,
+    ImageEncoding::Jpeg { .. } => DynamicImage::ImageRgb8(image.into_rgb8()),
+    ImageEncoding::WebP => DynamicImage::ImageRgba8(image.into_rgba8()),
+  };
+
+  let result = match encoding {
+    ImageEncoding::Png => image.write_to(&mut cursor, ImageFormat::Png),
+    ImageEncoding::Jpeg { quality } => image.write_with_encoder(JpegEncoder::new_with_quality(
+      &mut cursor,
+      quality.clamp(1, 100),
+    )),
+    ImageEncoding::WebP => image.write_with_encoder(WebPEncoder::new_lossless(&mut cursor)),
+  };
+
+  result
+    .inspect_err(|e| error!("Failed to convert image to {encoding:?}: {e}"))
+    .ok()?;
+
+  Some((buffer.into(), image))
+}
+
+/// Creates a PNG thumbnail that fits within a square of the given size. Smaller images are not scaled up.
+fn thumbnail(image: &DynamicImage, size: u32) -> Option<Bytes> {
+  let mut buffer = Vec::new();
+
+  let result = if image.width() <= size && image.height() <= size {
+    image.write_to(&mut Cursor::new(&mut buffer), ImageFormat::Png)
   } else {
-    error!("Failed to convert dib to png");
-    None
-  }
+    image
+      .thumbnail(size, size)
+      .write_to(&mut Cursor::new(&mut buffer), ImageFormat::Png)
+  };
+
+  result
+    .inspect_err(|e| error!("Failed to create image thumbnail: {e}"))
+    .ok()?;
+
+  Some(buffer.into())
 }
 
-pub(crate) fn convert_file_to_png(path: &Path) -> Option<Vec<u8>> {
-  let file_bytes = std::fs::read(path)
+/// Turns an image into a [`ClipboardImage`] (without a path), encoding it according to the options unless it can be kept as it is.
+///
+/// The original bytes are kept if they already have the target encoding, or if `keep_encoding` is set.
+pub(crate) fn process_image(source: SourceImage, options: &ImageOptions) -> Option<ClipboardImage> {
+  let encoding = options.encoding;
+
+  // The decoded image is only available if it had to be converted
+  let (bytes, mime_type, decoded) = match source {
+    SourceImage::Encoded { bytes, mime_type }
+      if options.keep_encoding || mime_type == encoding.mime_type() =>
+    {
+      // The buffer read from the clipboard is handed over without copying it
+      (Bytes::from(bytes), mime_type, None)
+    }
+    SourceImage::Encoded { bytes, mime_type } => {
+      let (bytes, image) = encode(load_image(&bytes, mime_type)?, encoding)?;
+      (bytes, encoding.mime_type(), Some(image))
+    }
+    #[cfg(windows)]
+    SourceImage::Dib(bytes) => {
+      let (bytes, image) = encode(decode_dib(&bytes)?, encoding)?;
+      (bytes, encoding.mime_type(), Some(image))
+    }
+    SourceImage::Processed(image) => return Some(image),
+  };
+
+  let (width, height, color_type) = match &decoded {
+    Some(image) => (image.width(), image.height(), image.color()),
+    None => read_metadata(&bytes, mime_type)?,
+  };
+
+  let thumbnail = options.thumbnail_size.and_then(|size| match &decoded {
+    Some(image) => thumbnail(image, size),
+    None => thumbnail(&load_image(&bytes, mime_type)?, size),
+  });
+
+  Some(ClipboardImage {
+    bytes,
+    mime_type,
+    width,
+    height,
+    color_type,
+    thumbnail,
+    path: None,
+  })
+}
+
+/// Reads an image file, if its format can be detected.
+pub(crate) fn read_image_file(path: &Path) -> Option<SourceImage> {
+  let bytes = std::fs::read(path)
     .inspect_err(|e| {
       error!(
         "Failed to read the contents of file `{}`: {e}",
@@ -35,22 +192,20 @@ pub(crate) fn convert_file_to_png(path: &Path) -> Option<Vec<u8>> {
     })
     .ok()?;
 
-  let dynamic_image = image::load_from_memory(&file_bytes)
-    .inspect_err(|e| {
+  let mime_type = image::guess_format(&bytes)
+    .ok()
+    .and_then(MimeType::from_image_format);
+
+  match mime_type {
+    Some(mime_type) => Some(SourceImage::Encoded { bytes, mime_type }),
+    None => {
       error!(
-        "Failed to create image from contents of file `{}`: {e}",
+        "Failed to create image from contents of file `{}`: unsupported format",
         path.display()
-      )
-    })
-    .ok()?;
-
-  let mut png_buffer = Vec::new();
-  dynamic_image
-    .write_to(&mut Cursor::new(&mut png_buffer), ImageFormat::Png)
-    .inspect_err(|e| error!("Failed to convert file `{}` to a png: {e}", path.display()))
-    .ok()?;
-
-  Some(png_buffer)
+      );
+      None
+    }
+  }
 }
 
 const IMAGE_FORMATS: [&str; 8] = ["png", "jpg", "jpeg", "gif", "bmp", "webp", "svg", "ico"];
@@ -60,26 +215,3 @@ pub(crate) fn file_is_image(path: &Path) -> bool {
     .extension()
     .is_some_and(|e| IMAGE_FORMATS.contains(&e.to_string_lossy().as_ref()))
 }
-
-#[cfg(target_os = "macos")]
-pub(crate) fn convert_tiff_to_png(tiff_bytes: &[u8]) -> Option<Vec<u8>> {
-  match image::load_from_memory_with_format(tiff_bytes, ImageFormat::Tiff) {
-    Ok(dynamic_image) => {
-      let mut png_buffer = Vec::new();
-      if dynamic_image
-        .write_to(&mut Cursor::new(&mut png_buffer), ImageFormat::Png)
-        .is_ok()
-      {
-        Some(png_buffer)
-      } else {
-        error!("Failed to convert tiff to png");
-
-        None
-      }
-    }
-    Err(e) => {
-      error!("Failed to convert tiff to png: {e}");
-      None
-    }
-  }
-}
@@ -0,0 +1,231 @@
+use std::{
+  collections::HashMap,
+  hash::{Hash, Hasher},
+  sync::{Arc, mpsc::Sender},
+};
+
+use futures::channel::oneshot;
+
+use crate::{
+  Body, ClipboardKind, FormatKind, error::ClipboardError, secrets::SecretDetector, sink::Request,
+};
+
+/// A format in which the content of a [`LazyContent`] can be fetched.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AvailableFormat {
+  /// The kind of content.
+  pub kind: FormatKind,
+  /// The name of the format, for [`FormatKind::Custom`].
+  pub name: Option<Arc<str>>,
+  /// The size of the content in bytes, if it could be known without reading it.
+  pub size: Option<usize>,
+}
+
+impl AvailableFormat {
+  fn of(body: &Body) -> Self {
+    let (name, size) = match body {
+      Body::Html(html) => (None, Some(html.markup().len())),
+      Body::Rtf(rtf) => (None, Some(rtf.len())),
+      Body::PlainText(text) => (None, Some(text.len())),
+      Body::Image(image) => (None, Some(image.bytes.len())),
+      Body::FileList(_) | Body::UriList(_) => (None, None),
+      Body::Custom { name, data } => (Some(name.clone()), Some(data.len())),
+    };
+
+    AvailableFormat {
+      kind: body.format_kind(),
+      name,
+      size,
+    }
+  }
+
+  fn matches(&self, body: &Body) -> bool {
+    match body {
+      Body::Custom { name, .. } => self.name.as_ref() == Some(name),
+      _ => self.kind == body.format_kind(),
+    }
+  }
+}
+
+/// Identifies a change detected by a backend, so that its content can be fetched later on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct ChangeId {
+  pub(crate) kind: ClipboardKind,
+  generation: u64,
+}
+
+/// Keeps track of the latest change of each kind of clipboard.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ChangeTracker {
+  generation: u64,
+  current: HashMap<ClipboardKind, u64>,
+}
+
+impl ChangeTracker {
+  /// Records a new change, which replaces the previous one for the same kind of clipboard.
+  pub(crate) fn record(&mut self, kind: ClipboardKind) -> ChangeId {
+    self.generation += 1;
+    self.current.insert(kind, self.generation);
+
+    ChangeId {
+      kind,
+      generation: self.generation,
+    }
+  }
+
+  /// Forgets the current change of a kind of clipboard, after its content was replaced.
+  pub(crate) fn invalidate(&mut self, kind: ClipboardKind) {
+    self.current.remove(&kind);
+  }
+
+  pub(crate) fn is_current(&self, change: &ChangeId) -> bool {
+    self.current.get(&change.kind) == Some(&change.generation)
+  }
+}
+
+#[derive(Debug, Clone)]
+enum Source {
+  /// The content is still on the clipboard, and it is read by the backend when fetched.
+  Clipboard {
+    change: ChangeId,
+    requests: Sender<Request>,
+  },
+  /// The content was received as a whole, so it is simply handed over when fetched.
+  Ready(Body),
+}
+
+/// A clipboard change whose content is only read when it is fetched, when
+/// [`lazy`](crate::ClipboardEventListenerBuilder::lazy) mode is enabled.
+///
+/// It lists the formats in which the content is available, in order of priority.
+///
+/// # Example
+/// ```no_run
+/// # use clipboard_watcher::{ClipboardContent, ClipboardEvent, FormatKind};
+/// # async fn handle(event: ClipboardEvent) {
+/// if let ClipboardContent::Lazy(content) = &event.content
+///   && let Some(format) = content.format(FormatKind::PlainText)
+/// {
+///     let body = content.fetch(format).await.unwrap();
+/// }
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct LazyContent {
+  formats: Vec<AvailableFormat>,
+  source: Source,
+  // Set if secrets are detected, since the content can only be scanned once it is fetched
+  secrets: Option<SecretDetector>,
+}
+
+impl LazyContent {
+  pub(crate) fn new(
+    change: ChangeId,
+    formats: Vec<AvailableFormat>,
+    requests: Sender<Request>,
+  ) -> Self {
+    LazyContent {
+      formats,
+      source: Source::Clipboard { change, requests },
+      secrets: None,
+    }
+  }
+
+  /// Wraps content that was already read, such as the one sent by a custom backend.
+  pub(crate) fn ready(body: Body) -> Self {
+    LazyContent {
+      formats: vec![AvailableFormat::of(&body)],
+      source: Source::Ready(body),
+      secrets: None,
+    }
+  }
+
+  /// Returns a copy of this content whose secrets are handled by the detector once it is fetched.
+  pub(crate) fn with_secrets(&self, secrets: SecretDetector) -> Self {
+    LazyContent {
+      formats: self.formats.clone(),
+      source: self.source.clone(),
+      secrets: Some(secrets),
+    }
+  }
+
+  /// Returns the formats in which the content is available, in order of priority.
+  pub fn formats(&self) -> &[AvailableFormat] {
+    &self.formats
+  }
+
+  /// Returns the first available format of the given kind, if there is one.
+  pub fn format(&self, kind: FormatKind) -> Option<&AvailableFormat> {
+    self.formats.iter().find(|format| format.kind == kind)
+  }
+
+  /// Reads the content in the given format from the clipboard.
+  ///
+  /// Fails with [`ClipboardError::ContentChanged`] if the clipboard has changed since this content was announced,
+  /// or with [`ClipboardError::NoMatchingFormat`] if the format is not one of the available [`formats`](Self::formats).
+  ///
+  /// If [secrets are detected](crate::ClipboardEventListenerBuilder::detect_secrets), the content is scanned once it is read,
+  /// and the [`SecretAction`](crate::SecretAction) is applied to it. Redacted content fails with [`ClipboardError::SecretFound`].
+  pub async fn fetch(&self, format: &AvailableFormat) -> Result<Body, ClipboardError> {
+    let body = self.read(format).await?;
+
+    match &self.secrets {
+      Some(secrets) => secrets.apply_fetched(body),
+      None => Ok(body),
+    }
+  }
+
+  async fn read(&self, format: &AvailableFormat) -> Result<Body, ClipboardError> {
+    if !self.formats.contains(format) {
+      return Err(ClipboardError::NoMatchingFormat);
+    }
+
+    match &self.source {
+      Source::Ready(body) if format.matches(body) => Ok(body.clone()),
+      Source::Ready(_) => Err(ClipboardError::NoMatchingFormat),
+      Source::Clipboard { change, requests } => {
+        let stopped =
+          || ClipboardError::ReadError("the clipboard monitor is not running".to_string());
+
+        let (reply, reply_rx) = oneshot::channel();
+
+        requests
+          .send(Request::Fetch {
+            change: *change,
+            format: format.clone(),
+            reply,
+          })
+          .map_err(|_| stopped())?;
+
+        reply_rx.await.map_err(|_| stopped())?
+      }
+    }
+  }
+}
+
+impl PartialEq for LazyContent {
+  fn eq(&self, other: &Self) -> bool {
+    self.formats == other.formats
+      && match (&self.source, &other.source) {
+        (Source::Clipboard { change, .. }, Source::Clipboard { change: other, .. }) => {
+          change == other
+        }
+        (Source::Ready(body), Source::Ready(other)) => body == other,
+        _ => false,
+      }
+  }
+}
+
+impl Eq for LazyContent {}
+
+impl Hash for LazyContent {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.formats.hash(state);
+
+    match &self.source {
+      Source::Clipboard { change, .. } => change.hash(state),
+      Source::Ready(body) => body.hash(state),
+    }
+  }
+}
@@ -9,19 +9,19 @@
 //! The following example shows how to receive clipboard items:
 //!
 //! ```no_run
-//! use clipboard_stream::{ClipboardEventListener, Body};
+//! use clipboard_watcher::{ClipboardEventListener, Body};
 //! use futures::stream::StreamExt;
 //!
 //! #[tokio::main]
 //! async fn main() {
 //!     // Spawn a clipboard event listener
-//!     let mut event_listener = ClipboardEventListener::spawn();
+//!     let mut event_listener = ClipboardEventListener::spawn().unwrap();
 //!
 //!     // Create a new stream
 //!     let mut stream = event_listener.new_stream(32);
 //!
-//!     while let Some(body) = stream.next().await {
-//!         if let Body::Utf8String(text) = body {
+//!     while let Some(Ok(event)) = stream.next().await {
+//!         if let Some(Body::PlainText(text)) = event.body() {
 //!             println!("{}", text);
 //!         }
 //!     }
@@ -35,27 +35,66 @@
 //! [`futures`](https://docs.rs/futures).
 //!
 //! # Platforms
+//! - Windows
 //! - macOS
+//! - Linux (Wayland, through the data-control protocol, and X11, through the XFixes extension or by polling the selection owners)
 //!
-//! Currently supported on **macOS only**. Windows support is planned for a future release.
+//! On Linux, the backend is picked automatically when the listener is spawned. See [`Backend`] for more information.
 //!
 //! [`Stream`]: https://docs.rs/futures/latest/futures/stream/trait.Stream.html
 //! [`ClipboardStream`]: crate::stream::ClipboardStream
+mod backend;
 mod body;
+mod classify;
 mod driver;
 pub mod error;
+mod event;
 mod event_listener;
+mod extraction;
+mod files;
+mod html;
 pub(crate) mod image;
+mod lazy;
 #[cfg(target_os = "macos")]
 mod macos;
+#[cfg(any(test, feature = "testing"))]
+mod mock;
 mod observer;
+mod rtf;
+mod secrets;
+mod sink;
 mod stream;
+#[cfg(all(unix, not(target_os = "macos")))]
+mod targets;
+mod text;
+#[cfg(all(unix, not(target_os = "macos")))]
+mod wayland;
 #[cfg(windows)]
 mod win;
+#[cfg(all(unix, not(target_os = "macos")))]
+mod x11;
 
+/// The color type of a [`ClipboardImage`], from the `image` crate.
+pub use ::image::ColorType;
+pub use bytes::Bytes;
+#[cfg(any(test, feature = "testing"))]
+pub use mock::{MockClipboard, MockClipboardHandle};
+pub use sink::ClipboardSink;
 pub use stream::{ClipboardStream, StreamId};
 
 pub use crate::{
-  body::{Body, MimeType},
-  event_listener::ClipboardEventListener,
+  backend::{Backend, ClipboardBackend},
+  body::{
+    Body, ClipboardImage, ClipboardSnapshot, FileEntry, FileMetadata, FormatKind, HtmlContent,
+    ImageEncoding, MimeType,
+  },
+  classify::{Classifier, ContentKind, Rgba},
+  event::{
+    ClipboardContent, ClipboardEvent, ClipboardKind, ConcealedPolicy, SkipReason, SkippedContent,
+    SourceApp,
+  },
+  event_listener::{ClipboardEventListener, ClipboardEventListenerBuilder},
+  lazy::{AvailableFormat, LazyContent},
+  secrets::{SecretAction, SecretKind, SecretMatch, SecretRule},
+  text::TextDecoding,
 };
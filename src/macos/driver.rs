@@ -1,30 +1,16 @@
-use std::{
-  sync::{atomic::AtomicBool, Arc},
-  time::Duration,
-};
-
 use crate::{
-  body::BodySenders, driver::Driver, error::ClipboardError, macos::observer::OSXObserver,
-  observer::Observer,
+  Backend, driver::Driver, error::ClipboardError, extraction::Extractor,
+  macos::observer::OSXObserver, observer::Observer, sink::ClipboardSink,
 };
 
 impl Driver {
   /// Construct [`Driver`] and spawn a thread for monitoring clipboard events
-  pub(crate) fn new(
-    body_senders: Arc<BodySenders>,
-    interval: Option<Duration>,
-    custom_formats: Vec<impl AsRef<str>>,
-    max_image_bytes: Option<usize>,
-    max_bytes: Option<usize>,
-  ) -> Result<Self, ClipboardError> {
-    let stop = Arc::new(AtomicBool::new(false));
+  pub(crate) fn new(sink: ClipboardSink, extractor: Extractor) -> Result<Self, ClipboardError> {
+    let stop = sink.stop_flag();
 
     let stop_cl = stop.clone();
 
-    let custom_formats: Vec<Arc<str>> = custom_formats
-      .into_iter()
-      .map(|fmt| fmt.as_ref().into())
-      .collect();
+    let interval = sink.interval();
 
     // spawn OS thread
     // observe clipboard change event and send item
@@ -32,21 +18,16 @@ impl Driver {
       // construct Observer in thread
       // OSXSys is **not** implemented Send + Sync
       // in order to send Observer, construct it
-      let mut observer = OSXObserver::new(
-        stop_cl,
-        interval,
-        custom_formats,
-        max_image_bytes,
-        max_bytes,
-      );
+      let mut observer = OSXObserver::new(stop_cl, interval, extractor);
 
       // event change observe loop
-      observer.observe(body_senders);
+      observer.observe(sink);
     });
 
     Ok(Driver {
       stop,
       handle: Some(handle),
+      backend: Backend::MacOs,
     })
   }
 }
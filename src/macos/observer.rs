@@ -13,59 +13,51 @@ use objc2::{
   rc::{Retained, autoreleasepool},
 };
 use objc2_app_kit::{
-  NSPasteboard, NSPasteboardType, NSPasteboardTypeHTML, NSPasteboardTypePNG,
-  NSPasteboardTypeString, NSPasteboardTypeTIFF, NSPasteboardURLReadingFileURLsOnlyKey,
+  NSPasteboard, NSPasteboardType, NSPasteboardTypeFileURL, NSPasteboardTypeHTML,
+  NSPasteboardTypePNG, NSPasteboardTypeRTF, NSPasteboardTypeString, NSPasteboardTypeTIFF,
+  NSPasteboardTypeURL, NSPasteboardURLReadingFileURLsOnlyKey,
 };
 use objc2_foundation::{NSArray, NSData, NSDictionary, NSNumber, NSString, NSURL};
+use url::Url;
 
 use crate::{
-  body::*,
+  AvailableFormat, Backend, Body, FormatKind, HtmlContent, MimeType,
   error::{ClipboardError, ExtractionError},
+  event::{ClipboardContent, ClipboardEvent, ClipboardKind},
+  extraction::{Extractor, FormatReader, Probe},
   image::*,
+  lazy::ChangeId,
   observer::Observer,
+  sink::{ClipboardSink, Request},
+  text::{TextDecoder, TextDecoding, TextEncoding},
 };
 
 pub(crate) struct OSXObserver {
   stop: Arc<AtomicBool>,
   pasteboard: Retained<NSPasteboard>,
   interval: Duration,
-  custom_formats: Vec<Arc<str>>,
-  max_image_size: Option<usize>,
-  max_size: Option<usize>,
+  extractor: Extractor,
+  // The change count of the pasteboard at the latest change
+  change_count: isize,
 }
 
 impl OSXObserver {
-  pub(super) fn new(
-    stop: Arc<AtomicBool>,
-    interval: Option<Duration>,
-    custom_formats: Vec<Arc<str>>,
-    max_image_size: Option<usize>,
-    max_size: Option<usize>,
-  ) -> Self {
+  pub(super) fn new(stop: Arc<AtomicBool>, interval: Duration, extractor: Extractor) -> Self {
     let pasteboard = unsafe { NSPasteboard::generalPasteboard() };
-
-    let max_image_size = if max_image_size.is_none() && max_size.is_some() {
-      debug!("Using global size limit for images...");
-      max_size
-    } else {
-      max_image_size
-    };
+    let change_count = unsafe { pasteboard.changeCount() };
 
     OSXObserver {
       stop,
       pasteboard,
-      interval: interval.unwrap_or_else(|| std::time::Duration::from_millis(200)),
-      custom_formats,
-      max_image_size,
-      max_size,
+      interval,
+      extractor,
+      change_count,
     }
   }
 }
 
 impl Observer for OSXObserver {
-  fn observe(&mut self, body_senders: Arc<BodySenders>) {
-    let mut last_count = self.get_change_count();
-
+  fn observe(&mut self, sink: ClipboardSink) {
     let interval = self.interval;
 
     info!("Started monitoring the clipboard");
@@ -73,16 +65,23 @@ impl Observer for OSXObserver {
     while !self.stop.load(Ordering::Relaxed) {
       std::thread::sleep(interval);
 
+      while let Some(request) = sink.next_request() {
+        self.handle_request(request);
+      }
+
       let change_count = self.get_change_count();
 
-      if change_count != last_count {
-        last_count = change_count;
+      if change_count != self.change_count {
+        self.change_count = change_count;
 
         match self.get_clipboard_content() {
-          Ok(Some(content)) => body_senders.send_all(Ok(Arc::new(content))),
+          Ok(Some(content)) => sink.send_event(
+            ClipboardEvent::new(ClipboardKind::Clipboard, content)
+              .with_change_counter(Some(change_count as u64)),
+          ),
           Err(e) => {
             error!("{e}");
-            body_senders.send_all(Err(e));
+            sink.send_error(e);
           }
           // Found content but ignored it (empty or beyond allowed size)
           Ok(None) => {}
@@ -97,6 +96,60 @@ impl OSXObserver {
     unsafe { self.pasteboard.changeCount() }
   }
 
+  fn get_clipboard_content(&mut self) -> Result<Option<ClipboardContent>, ClipboardError> {
+    autoreleasepool(|_| {
+      self.extractor.get_clipboard_content(
+        ClipboardKind::Clipboard,
+        &mut PasteboardFormats {
+          pasteboard: &self.pasteboard,
+          text_decoding: self.extractor.text_decoding(),
+        },
+      )
+    })
+  }
+
+  fn handle_request(&mut self, request: Request) {
+    match request {
+      // Writing is refused by the listener, so this is never reached
+      Request::Set { reply, .. } => {
+        let _ = reply.send(Err(ClipboardError::Unsupported(Backend::MacOs)));
+      }
+      Request::Fetch {
+        change,
+        format,
+        reply,
+      } => {
+        let _ = reply.send(self.fetch(&change, &format));
+      }
+    }
+  }
+
+  /// Reads the content of a lazy change from the pasteboard.
+  fn fetch(&self, change: &ChangeId, format: &AvailableFormat) -> Result<Body, ClipboardError> {
+    if self.get_change_count() != self.change_count {
+      return Err(ClipboardError::ContentChanged);
+    }
+
+    autoreleasepool(|_| {
+      self.extractor.fetch(
+        change,
+        format,
+        &mut PasteboardFormats {
+          pasteboard: &self.pasteboard,
+          text_decoding: self.extractor.text_decoding(),
+        },
+      )
+    })
+  }
+}
+
+/// Reads each kind of content from the pasteboard.
+struct PasteboardFormats<'a> {
+  pasteboard: &'a NSPasteboard,
+  text_decoding: TextDecoding,
+}
+
+impl PasteboardFormats<'_> {
   fn extract_clipboard_format(
     &self,
     format_type: &NSPasteboardType,
@@ -116,7 +169,7 @@ impl OSXObserver {
           // Check the size limit. If exceeded, return Err to signal an early exit.
           if let Some(limit) = max_size {
             if size > limit {
-              return Err(ExtractionError::SizeTooLarge);
+              return Err(ExtractionError::SizeTooLarge(Some(size)));
             }
           }
 
@@ -128,7 +181,44 @@ impl OSXObserver {
     })
   }
 
-  pub(crate) fn extract_files_list(&self) -> Result<Option<Vec<PathBuf>>, ExtractionError> {
+  fn extract_uri_list(&self) -> Result<Option<Vec<Url>>, ExtractionError> {
+    let uris = autoreleasepool(|_| {
+      let class_array = NSArray::from_slice(&[NSURL::class()]);
+
+      let objects = unsafe {
+        self
+          .pasteboard
+          .readObjectsForClasses_options(&class_array, None)
+      };
+
+      objects.map(|array| {
+        array
+          .iter()
+          .filter_map(|obj| {
+            obj.downcast::<NSURL>().ok().and_then(|url| {
+              if unsafe { url.isFileURL() } {
+                None
+              } else {
+                unsafe { url.absoluteString() }.and_then(|s| Url::parse(&s.to_string()).ok())
+              }
+            })
+          })
+          .collect::<Vec<_>>()
+      })
+    });
+
+    match uris {
+      // Only local files were found
+      Some(uris) if uris.is_empty() => Ok(None),
+      Some(uris) => {
+        debug!("Found URI list");
+        Ok(Some(uris))
+      }
+      None => Ok(None),
+    }
+  }
+
+  fn extract_files_list(&self) -> Result<Option<Vec<PathBuf>>, ExtractionError> {
     let files = autoreleasepool(|_| {
       let class_array = NSArray::from_slice(&[NSURL::class()]);
       let options = NSDictionary::from_slices(
@@ -159,38 +249,36 @@ impl OSXObserver {
     });
 
     match files {
+      // The URLs on the pasteboard (if any) are not local files
+      Some(files) if files.is_empty() => Ok(None),
       Some(files) => {
-        if files.is_empty() {
-          // Found list but it was empty, trigger early exit
-          Err(ExtractionError::EmptyContent)
-        } else {
-          debug!("Found file list");
-          Ok(Some(files))
-        }
+        debug!("Found file list");
+        Ok(Some(files))
       }
       None => Ok(None),
     }
   }
 
-  pub(super) fn extract_image_bytes(&self) -> Result<Option<Vec<u8>>, ExtractionError> {
-    let max_image_size = self.max_image_size;
-
+  fn extract_image(
+    &self,
+    max_image_size: Option<usize>,
+  ) -> Result<Option<SourceImage>, ExtractionError> {
     if let Some(png_bytes) =
       unsafe { self.extract_clipboard_format(NSPasteboardTypePNG, max_image_size)? }
     {
       debug!("Loaded png from clipboard");
-      Ok(Some(png_bytes))
+      Ok(Some(SourceImage::Encoded {
+        bytes: png_bytes,
+        mime_type: MimeType::Png,
+      }))
     } else if let Some(tiff_bytes) =
       unsafe { self.extract_clipboard_format(NSPasteboardTypeTIFF, max_image_size)? }
     {
-      debug!("Loaded TIFF from clipboard. Converting to PNG...");
-
-      if let Some(png_bytes) = convert_tiff_to_png(&tiff_bytes) {
-        Ok(Some(png_bytes))
-      } else {
-        // We got the content but failed to extract it, trigger early exit
-        Err(ExtractionError::ConversionError)
-      }
+      debug!("Loaded TIFF from clipboard");
+      Ok(Some(SourceImage::Encoded {
+        bytes: tiff_bytes,
+        mime_type: MimeType::Tiff,
+      }))
     } else {
       Ok(None)
     }
@@ -219,96 +307,131 @@ impl OSXObserver {
       Ok(None)
     })
   }
+}
 
-  fn extract_content(&self) -> Result<Option<Body>, ExtractionError> {
-    autoreleasepool(|_| {
-      let max_size = self.max_size;
-
-      for name in self.custom_formats.iter() {
-        let format_nsstring = NSString::from_str(name.as_ref());
-        // For custom formats, we check the size as well as the presence
-        if let Some(bytes) = self.extract_clipboard_format(&format_nsstring, max_size)? {
-          debug!("Found content with custom format `{name}`");
-
-          return Ok(Some(Body::Custom {
-            name: name.clone(),
-            data: bytes,
-          }));
-        }
-      }
+impl PasteboardFormats<'_> {
+  fn has_type(&self, types: &[&NSPasteboardType]) -> bool {
+    let types = NSArray::from_slice(types);
 
-      if let Some(image_bytes) = self.extract_image_bytes()? {
-        // If there is only one path in the file list, which is sometimes emitted by the OS
-        // when copying an image, we assign it to the image
-        let image_path = if let Some(mut files_list) = self.extract_files_list()? {
-          if files_list.len() == 1 {
-            Some(files_list.remove(0))
-          } else {
-            None
-          }
-        } else {
-          None
-        };
-
-        Ok(Some(Body::Image(ClipboardImage {
-          bytes: image_bytes,
-          path: image_path,
-        })))
-      } else if let Some(mut files_list) = self.extract_files_list()? {
-        // We check if there is only one file in the list
-        if files_list.len() == 1
-          && let Some(path) = files_list.first()
-          // Then, if it's an image
-          && file_is_image(path)
-          // Then, if the size is within the allowed range
-          && self.max_image_size.is_none_or(|max| path.metadata().is_ok_and(|metadata| max as u64 > metadata.len()))
-          // Then, if the bytes are readable and the conversion to png is successful
-          && let Some(png_bytes) = convert_file_to_png(path)
-        //
-        // Only if all of these are true, we save it as an image
-        {
-          let image_path = files_list.remove(0);
-
-          Ok(Some(Body::Image(ClipboardImage {
-            bytes: png_bytes,
-            path: Some(image_path),
-          })))
-        } else {
-          Ok(Some(Body::FileList(files_list)))
-        }
-      } else {
-        if let Some(html) = unsafe { self.string_from_type(NSPasteboardTypeHTML)? } {
-          debug!("Extracted HTML content from clipboard");
-          return Ok(Some(Body::Html(html)));
-        }
-        if let Some(plain) = unsafe { self.string_from_type(NSPasteboardTypeString)? } {
-          debug!("Extracted plain text from clipboard");
-          return Ok(Some(Body::PlainText(plain)));
-        }
+    unsafe { self.pasteboard.availableTypeFromArray(&types) }.is_some()
+  }
 
-        Ok(None)
+  /// Decodes the raw representations of the text, for when the pasteboard cannot read it as a string.
+  fn decode_text(&self) -> Result<Option<String>, ExtractionError> {
+    let mut decoder = TextDecoder::new(self.text_decoding);
+
+    let utf16_external = NSString::from_str("public.utf16-external-plain-text");
+    let utf16 = NSString::from_str("public.utf16-plain-text");
+
+    for (type_, encoding) in [
+      (&*utf16_external, TextEncoding::Utf16),
+      (&*utf16, TextEncoding::Utf16),
+      (unsafe { NSPasteboardTypeString }, TextEncoding::Utf8),
+    ] {
+      if let Some(bytes) = self.extract_clipboard_format(type_, None)?
+        && let Some(text) = decoder.decode(bytes, encoding)
+      {
+        return Ok(Some(text));
       }
-    })
+    }
+
+    decoder.finish()
   }
+}
 
-  fn get_clipboard_content(&self) -> Result<Option<Body>, ClipboardError> {
-    match self.extract_content() {
-      // Found content
-      Ok(Some(content)) => Ok(Some(content)),
-      // Non-fatal errors, we just return None
-      Err(ExtractionError::EmptyContent) => {
-        debug!("Found empty content, skipping it...");
-        Ok(None)
-      }
-      Err(ExtractionError::SizeTooLarge) => {
-        debug!("Found content beyond allowed size, skipping it...");
-        Ok(None)
+impl FormatReader for PasteboardFormats<'_> {
+  fn custom_format(
+    &mut self,
+    name: &str,
+    max_size: Option<usize>,
+  ) -> Result<Option<Vec<u8>>, ExtractionError> {
+    let format_nsstring = NSString::from_str(name);
+    // For custom formats, we check the size as well as the presence
+    self.extract_clipboard_format(&format_nsstring, max_size)
+  }
+
+  fn image(
+    &mut self,
+    max_image_size: Option<usize>,
+  ) -> Result<Option<SourceImage>, ExtractionError> {
+    self.extract_image(max_image_size)
+  }
+
+  fn files_list(
+    &mut self,
+    _max_size: Option<usize>,
+  ) -> Result<Option<Vec<PathBuf>>, ExtractionError> {
+    self.extract_files_list()
+  }
+
+  fn html(&mut self) -> Result<Option<HtmlContent>, ExtractionError> {
+    // The pasteboard does not record the page that the content was copied from
+    let html = unsafe { self.string_from_type(NSPasteboardTypeHTML)? }
+      .map(|markup| HtmlContent::from_markup(markup, None));
+
+    if html.is_some() {
+      debug!("Extracted HTML content from clipboard");
+    }
+
+    Ok(html)
+  }
+
+  fn uri_list(&mut self, _max_size: Option<usize>) -> Result<Option<Vec<Url>>, ExtractionError> {
+    self.extract_uri_list()
+  }
+
+  fn rtf(&mut self) -> Result<Option<String>, ExtractionError> {
+    let rtf = unsafe { self.extract_clipboard_format(NSPasteboardTypeRTF, None)? }
+      .map(|bytes| String::from_utf8_lossy(&bytes).into_owned());
+
+    if rtf.is_some() {
+      debug!("Extracted RTF content from clipboard");
+    }
+
+    Ok(rtf)
+  }
+
+  fn plain_text(&mut self) -> Result<Option<String>, ExtractionError> {
+    let plain = match unsafe { self.string_from_type(NSPasteboardTypeString)? } {
+      Some(text) => Some(text),
+      // The text could not be read as a string, which happens when it is not valid UTF-8
+      None => self.decode_text()?,
+    };
+
+    if plain.is_some() {
+      debug!("Extracted plain text from clipboard");
+    }
+
+    Ok(plain)
+  }
+
+  fn probe(&mut self, kind: FormatKind, name: Option<&str>) -> Result<Probe, ExtractionError> {
+    // The pasteboard only gives the size of the data by reading it
+    let available = autoreleasepool(|_| unsafe {
+      match kind {
+        FormatKind::Custom => name.is_some_and(|name| self.has_type(&[&NSString::from_str(name)])),
+        FormatKind::Image => self.has_type(&[NSPasteboardTypePNG, NSPasteboardTypeTIFF]),
+        FormatKind::FileList => self.has_type(&[NSPasteboardTypeFileURL]),
+        FormatKind::Html => self.has_type(&[NSPasteboardTypeHTML]),
+        // Local files are also URLs, which are only told apart once read
+        FormatKind::UriList => self.has_type(&[NSPasteboardTypeURL, NSPasteboardTypeFileURL]),
+        FormatKind::Rtf => self.has_type(&[NSPasteboardTypeRTF]),
+        FormatKind::PlainText => self.has_type(&[NSPasteboardTypeString]),
       }
+    });
 
-      // Actual error, we send it
-      Err(ExtractionError::ConversionError) => Err(ClipboardError::ImageConversion),
-      // There was content but we could not read it
-      Ok(None) => Err(ClipboardError::NoMatchingFormat),
+    if available {
+      Ok(Probe::Available { size: None })
+    } else {
+      Ok(Probe::Missing)
     }
   }
+
+  fn is_concealed(&mut self) -> bool {
+    // The markers defined by nspasteboard.org, which password managers set on secrets
+    let concealed = NSString::from_str("org.nspasteboard.ConcealedType");
+    let transient = NSString::from_str("org.nspasteboard.TransientType");
+
+    autoreleasepool(|_| self.has_type(&[&concealed, &transient]))
+  }
 }
@@ -0,0 +1,179 @@
+use std::{
+  collections::HashMap,
+  sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+};
+
+use log::error;
+
+use crate::{
+  Backend, Body, ClipboardBackend, ClipboardEvent, ClipboardKind, ClipboardSink,
+  error::ClipboardError,
+  extraction::Extractor,
+  sink::{BodyReader, Request},
+};
+
+enum MockItem {
+  Content(ClipboardKind, BodyReader),
+  Error(ClipboardError),
+}
+
+/// An in-memory [`ClipboardBackend`], which does not interact with the system clipboard.
+///
+/// The items pushed through its [`MockClipboardHandle`] are read in the same way as the content of a real clipboard,
+/// so they go through the same options (such as size limits, format priority, lazy mode and the handling of secrets).
+/// Images are delivered as they are, without being decoded.
+///
+/// # Example
+/// ```
+/// use clipboard_watcher::{Body, ClipboardEventListener, MockClipboard};
+/// use futures::StreamExt;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let (mock, handle) = MockClipboard::new();
+///
+/// let mut event_listener = ClipboardEventListener::builder().backend(mock).spawn().unwrap();
+/// let mut stream = event_listener.new_stream(32);
+///
+/// handle.push(Body::PlainText("Hello!".to_string()));
+///
+/// let event = stream.next().await.unwrap().unwrap();
+/// assert_eq!(event.body(), Some(&Body::PlainText("Hello!".to_string())));
+/// # }
+/// ```
+pub struct MockClipboard {
+  rx: Receiver<MockItem>,
+  contents: HashMap<ClipboardKind, BodyReader>,
+}
+
+/// The handle used to simulate the changes of a [`MockClipboard`].
+#[derive(Clone)]
+pub struct MockClipboardHandle {
+  tx: Sender<MockItem>,
+}
+
+impl MockClipboard {
+  /// Creates a new [`MockClipboard`], along with the handle used to push items into it.
+  pub fn new() -> (Self, MockClipboardHandle) {
+    let (tx, rx) = mpsc::channel();
+
+    (
+      MockClipboard {
+        rx,
+        contents: HashMap::new(),
+      },
+      MockClipboardHandle { tx },
+    )
+  }
+}
+
+impl MockClipboardHandle {
+  /// Simulates a change of the regular clipboard.
+  pub fn push(&self, body: Body) {
+    self.push_kind(ClipboardKind::Clipboard, body);
+  }
+
+  /// Simulates a change of the given kind of clipboard.
+  pub fn push_kind(&self, kind: ClipboardKind, body: Body) {
+    self.send(MockItem::Content(
+      kind,
+      BodyReader {
+        body,
+        concealed: false,
+      },
+    ));
+  }
+
+  /// Simulates a change of the regular clipboard made by a password manager, which marks the content as secret.
+  ///
+  /// It is handled according to the [`ConcealedPolicy`](crate::ConcealedPolicy) of the listener.
+  pub fn push_concealed(&self, body: Body) {
+    self.send(MockItem::Content(
+      ClipboardKind::Clipboard,
+      BodyReader {
+        body,
+        concealed: true,
+      },
+    ));
+  }
+
+  /// Simulates an error of the clipboard.
+  pub fn push_error(&self, error: ClipboardError) {
+    self.send(MockItem::Error(error));
+  }
+
+  fn send(&self, item: MockItem) {
+    // The mock is only gone if the listener was dropped, so there is nobody left to notify
+    let _ = self.tx.send(item);
+  }
+}
+
+impl MockClipboard {
+  fn handle_item(&mut self, item: MockItem, extractor: &mut Extractor, sink: &ClipboardSink) {
+    let (kind, content) = match item {
+      MockItem::Content(kind, content) => (kind, content),
+      MockItem::Error(error) => return sink.send_error(error),
+    };
+
+    if !sink.kinds().contains(&kind) {
+      return;
+    }
+
+    let content = self.contents.entry(kind).insert_entry(content).into_mut();
+
+    match extractor.get_clipboard_content(kind, content) {
+      Ok(Some(content)) => sink.send_event(ClipboardEvent::new(kind, content)),
+      Err(e) => {
+        error!("{e}");
+        sink.send_error(e);
+      }
+      // Found content but ignored it (empty or beyond allowed size)
+      Ok(None) => {}
+    }
+  }
+
+  fn handle_request(&mut self, request: Request, extractor: &Extractor) {
+    match request {
+      // Writing is refused by the listener, so this is never reached
+      Request::Set { reply, .. } => {
+        let _ = reply.send(Err(ClipboardError::Unsupported(Backend::Custom)));
+      }
+      Request::Fetch {
+        change,
+        format,
+        reply,
+      } => {
+        let result = match self.contents.get_mut(&change.kind) {
+          Some(content) => extractor.fetch(&change, &format, content),
+          None => Err(ClipboardError::ContentChanged),
+        };
+
+        let _ = reply.send(result);
+      }
+    }
+  }
+}
+
+impl ClipboardBackend for MockClipboard {
+  fn observe(&mut self, sink: ClipboardSink) {
+    let mut extractor = sink.extractor();
+
+    while !sink.is_stopped() {
+      match self.rx.recv_timeout(sink.interval()) {
+        Ok(item) => self.handle_item(item, &mut extractor, &sink),
+        Err(RecvTimeoutError::Timeout) => {}
+        // Every handle was dropped, so nothing else can be pushed
+        Err(RecvTimeoutError::Disconnected) => std::thread::sleep(sink.interval()),
+      }
+
+      while let Some(request) = sink.next_request() {
+        // The items pushed before the request was made must be seen by it
+        while let Ok(item) = self.rx.try_recv() {
+          self.handle_item(item, &mut extractor, &sink);
+        }
+
+        self.handle_request(request, &extractor);
+      }
+    }
+  }
+}
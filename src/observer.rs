@@ -1,8 +1,6 @@
-use std::sync::Arc;
-
-use crate::body::BodySenders;
+use crate::sink::ClipboardSink;
 
 /// A trait observing clipboard change event and send data to receiver([`ClipboardStream`])
 pub(super) trait Observer {
-  fn observe(&mut self, body_senders: Arc<BodySenders>);
+  fn observe(&mut self, sink: ClipboardSink);
 }
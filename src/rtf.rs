@@ -0,0 +1,292 @@
+//! A minimal conversion from the Rich Text Format to plain text, which keeps the text and drops the formatting.
+
+use std::{iter::Peekable, str::Chars};
+
+/// Destinations that hold metadata rather than text, so their content is skipped.
+const SKIPPED_DESTINATIONS: [&str; 14] = [
+  "fonttbl",
+  "colortbl",
+  "stylesheet",
+  "info",
+  "pict",
+  "object",
+  "header",
+  "footer",
+  "headerl",
+  "headerr",
+  "footerl",
+  "footerr",
+  "listtable",
+  "listoverridetable",
+];
+
+/// The characters of Windows-1252 between `0x80` and `0x9f`, where it differs from ISO 8859-1.
+/// The five unassigned bytes are kept as the control characters with the same code.
+const WINDOWS_1252_HIGH: [char; 32] = [
+  '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8d}', 'Ž', '\u{8f}',
+  '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9d}', 'ž', 'Ÿ',
+];
+
+/// The code page of the document when it does not declare one with `\ansicpg`.
+const DEFAULT_CODEPAGE: i32 = 1252;
+
+/// Decodes the bytes given with `\'hh` escapes, according to the code page of the document.
+///
+/// Only Windows-1252, ISO 8859-1 and UTF-8 are supported. With other code pages, the bytes outside of ASCII are replaced with `U+FFFD`.
+fn decode_codepage(bytes: &[u8], codepage: i32) -> String {
+  match codepage {
+    65001 => String::from_utf8_lossy(bytes).into_owned(),
+    28591 => bytes.iter().copied().map(char::from).collect(),
+    1252 => bytes
+      .iter()
+      .map(|&byte| match byte {
+        0x80..=0x9f => WINDOWS_1252_HIGH[usize::from(byte - 0x80)],
+        _ => char::from(byte),
+      })
+      .collect(),
+    _ => bytes
+      .iter()
+      .map(|&byte| {
+        if byte.is_ascii() {
+          char::from(byte)
+        } else {
+          char::REPLACEMENT_CHARACTER
+        }
+      })
+      .collect(),
+  }
+}
+
+struct TextWriter {
+  text: String,
+  // The code page of the bytes given with `\'hh`
+  codepage: i32,
+  // The bytes given with `\'hh` that were not decoded yet, since a character can span several of them
+  bytes: Vec<u8>,
+  // Whether the current group is a skipped destination
+  skip: bool,
+  // The number of fallback characters that follow a unicode character
+  unicode_skip: usize,
+  // The fallback characters that are left to skip
+  pending_skip: usize,
+}
+
+impl TextWriter {
+  /// Checks whether the next character is skipped, which uses up one of the fallback characters.
+  fn skips_next(&mut self) -> bool {
+    if self.skip {
+      return true;
+    }
+
+    if self.pending_skip > 0 {
+      self.pending_skip -= 1;
+      return true;
+    }
+
+    false
+  }
+
+  fn push(&mut self, c: char) {
+    if self.skips_next() {
+      return;
+    }
+
+    self.flush();
+    self.text.push(c);
+  }
+
+  fn push_byte(&mut self, byte: u8) {
+    if !self.skips_next() {
+      self.bytes.push(byte);
+    }
+  }
+
+  /// Decodes the pending bytes.
+  fn flush(&mut self) {
+    if !self.bytes.is_empty() {
+      let text = decode_codepage(&self.bytes, self.codepage);
+      self.text.push_str(&text);
+      self.bytes.clear();
+    }
+  }
+}
+
+fn read_control_word(chars: &mut Peekable<Chars>) -> (String, Option<i32>) {
+  let mut word = String::new();
+
+  while let Some(c) = chars.next_if(char::is_ascii_alphabetic) {
+    word.push(c);
+  }
+
+  let mut param = String::new();
+
+  if let Some(c) = chars.next_if_eq(&'-') {
+    param.push(c);
+  }
+
+  while let Some(c) = chars.next_if(char::is_ascii_digit) {
+    param.push(c);
+  }
+
+  // A space that delimits the control word is part of it
+  chars.next_if_eq(&' ');
+
+  (word, param.parse().ok())
+}
+
+/// Extracts the text of an RTF document.
+pub(crate) fn rtf_to_text(rtf: &str) -> String {
+  let mut writer = TextWriter {
+    text: String::new(),
+    codepage: DEFAULT_CODEPAGE,
+    bytes: Vec::new(),
+    skip: false,
+    unicode_skip: 1,
+    pending_skip: 0,
+  };
+  // The state of the enclosing groups
+  let mut groups = Vec::new();
+  let mut chars = rtf.chars().peekable();
+
+  while let Some(c) = chars.next() {
+    match c {
+      '{' => groups.push((writer.skip, writer.unicode_skip)),
+      '}' => {
+        if let Some((skip, unicode_skip)) = groups.pop() {
+          writer.skip = skip;
+          writer.unicode_skip = unicode_skip;
+        }
+      }
+      '\\' => match chars.peek().copied() {
+        Some(escaped @ ('\\' | '{' | '}')) => {
+          chars.next();
+          writer.push(escaped);
+        }
+        // A character given by its hexadecimal code
+        Some('\'') => {
+          chars.next();
+          let hex: String = chars.by_ref().take(2).collect();
+
+          if let Ok(byte) = u8::from_str_radix(&hex, 16) {
+            writer.push_byte(byte);
+          }
+        }
+        // An optional destination, which is skipped if not supported
+        Some('*') => {
+          chars.next();
+          writer.skip = true;
+        }
+        Some('~') => {
+          chars.next();
+          writer.push('\u{a0}');
+        }
+        Some('_') => {
+          chars.next();
+          writer.push('-');
+        }
+        Some(c) if c.is_ascii_alphabetic() => {
+          let (word, param) = read_control_word(&mut chars);
+
+          match word.as_str() {
+            "par" | "line" => writer.push('\n'),
+            "tab" => writer.push('\t'),
+            "u" => {
+              if let Some(code) = param {
+                // Codes above 32767 are written as negative numbers
+                let code = if code < 0 { code + 65536 } else { code };
+
+                if let Some(c) = char::from_u32(code as u32) {
+                  writer.push(c);
+                }
+
+                writer.pending_skip = writer.unicode_skip;
+              }
+            }
+            "ansicpg" => writer.codepage = param.unwrap_or(DEFAULT_CODEPAGE),
+            "uc" => writer.unicode_skip = param.unwrap_or(1).max(0) as usize,
+            word if SKIPPED_DESTINATIONS.contains(&word) => writer.skip = true,
+            _ => {}
+          }
+        }
+        // Other control symbols have no textual meaning
+        Some(_) => {
+          chars.next();
+        }
+        None => {}
+      },
+      // Line breaks in the source are not part of the text
+      '\r' | '\n' => {}
+      c => writer.push(c),
+    }
+  }
+
+  writer.flush();
+
+  writer.text.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn keeps_text_and_drops_formatting() {
+    assert_eq!(
+      rtf_to_text(r"{\rtf1\ansi {\b Hello}\par World\tab!}"),
+      "Hello\nWorld\t!"
+    );
+  }
+
+  #[test]
+  fn escaped_symbols() {
+    assert_eq!(
+      rtf_to_text(r"{\rtf1 a\{b\}c\\d\~e\_f}"),
+      "a{b}c\\d\u{a0}e-f"
+    );
+  }
+
+  #[test]
+  fn hex_escapes_default_to_windows_1252() {
+    assert_eq!(
+      rtf_to_text(r"{\rtf1\ansi \'93quoted\'94 \'96 caf\'e9 \'80}"),
+      "“quoted” – café €"
+    );
+  }
+
+  #[test]
+  fn hex_escapes_follow_the_code_page() {
+    assert_eq!(
+      rtf_to_text(r"{\rtf1\ansi\ansicpg65001 caf\'c3\'a9}"),
+      "café"
+    );
+    assert_eq!(rtf_to_text(r"{\rtf1\ansi\ansicpg28591 \'93}"), "\u{93}");
+    assert_eq!(rtf_to_text(r"{\rtf1\ansi\ansicpg1251 a\'e9}"), "a\u{fffd}");
+  }
+
+  #[test]
+  fn unicode_skips_its_fallback() {
+    assert_eq!(rtf_to_text(r"{\rtf1 caf\u233?!}"), "café!");
+    assert_eq!(rtf_to_text(r"{\rtf1 caf\u233\'e9!}"), "café!");
+  }
+
+  #[test]
+  fn unicode_follows_the_fallback_length() {
+    assert_eq!(rtf_to_text(r"{\rtf1\uc2\u8364 EU!}"), "€!");
+    assert_eq!(rtf_to_text(r"{\rtf1\uc0\u8364 !}"), "€!");
+    // The length only applies to the group where it is set
+    assert_eq!(rtf_to_text(r"{\rtf1{\uc2\u8364 EU}\u8364 E!}"), "€€!");
+  }
+
+  #[test]
+  fn negative_unicode_codes() {
+    // U+FFFD, written as 65533 - 65536
+    assert_eq!(rtf_to_text(r"{\rtf1 \u-3?}"), "\u{fffd}");
+  }
+
+  #[test]
+  fn skips_metadata_and_ignorable_destinations() {
+    let rtf = r"{\rtf1\ansi{\fonttbl{\f0 Arial;}}{\colortbl;\red0\green0\blue0;}{\*\generator Writer;}{\info{\title Doc}}Text{\*\unknown ignored {nested}} end}";
+
+    assert_eq!(rtf_to_text(rtf), "Text end");
+  }
+}
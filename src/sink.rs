@@ -0,0 +1,298 @@
+use std::{
+  path::PathBuf,
+  sync::{
+    Arc, Mutex,
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    mpsc::Receiver,
+  },
+  time::Duration,
+};
+
+use futures::channel::oneshot;
+use log::{debug, error};
+use url::Url;
+
+use crate::{
+  Body, ClipboardEvent, ClipboardEventListenerBuilder, ClipboardKind, FormatKind, HtmlContent,
+  SourceApp,
+  body::BodySenders,
+  classify::Classifiers,
+  error::{ClipboardError, ExtractionError},
+  extraction::{Extractor, FormatReader, Probe},
+  image::SourceImage,
+  lazy::{AvailableFormat, ChangeId},
+  secrets::SecretDetector,
+};
+
+/// A request sent by the listener to its backend, which is handled in the thread of the backend.
+pub(crate) enum Request {
+  // Writing is only supported by the Linux backends
+  #[cfg_attr(not(all(unix, not(target_os = "macos"))), allow(dead_code))]
+  Set {
+    kind: ClipboardKind,
+    body: Body,
+    reply: oneshot::Sender<Result<(), ClipboardError>>,
+  },
+  /// Reads the content of a lazy change.
+  Fetch {
+    change: ChangeId,
+    format: AvailableFormat,
+    reply: oneshot::Sender<Result<Body, ClipboardError>>,
+  },
+}
+
+/// The receiving end for the clipboard changes detected by a backend.
+///
+/// It applies the options of the [`ClipboardEventListenerBuilder`](crate::ClipboardEventListenerBuilder)
+/// to the items that it receives, and delivers them to every [`ClipboardStream`](crate::ClipboardStream) of the listener.
+#[derive(Debug, Clone)]
+pub struct ClipboardSink {
+  body_senders: Arc<BodySenders>,
+  requests: Arc<Mutex<Receiver<Request>>>,
+  stop: Arc<AtomicBool>,
+  interval: Duration,
+  kinds: Arc<[ClipboardKind]>,
+  ignored_apps: Arc<[String]>,
+  ignore_own_writes: bool,
+  classifiers: Arc<Classifiers>,
+  secrets: Arc<SecretDetector>,
+  sequence: Arc<AtomicU64>,
+  extractor: Extractor,
+}
+
+impl ClipboardSink {
+  pub(crate) fn new(
+    body_senders: Arc<BodySenders>,
+    requests: Receiver<Request>,
+    extractor: Extractor,
+    options: &ClipboardEventListenerBuilder,
+  ) -> Self {
+    ClipboardSink {
+      body_senders,
+      requests: Arc::new(Mutex::new(requests)),
+      stop: Arc::new(AtomicBool::new(false)),
+      interval: options
+        .interval
+        .unwrap_or_else(|| Duration::from_millis(200)),
+      kinds: options.kinds.as_slice().into(),
+      ignored_apps: options.ignored_apps.as_slice().into(),
+      ignore_own_writes: options.ignore_own_writes,
+      classifiers: Arc::new(options.classifiers.clone()),
+      secrets: Arc::new(options.secrets.clone()),
+      sequence: Arc::new(AtomicU64::new(0)),
+      extractor,
+    }
+  }
+
+  #[cfg_attr(not(all(unix, not(target_os = "macos"))), allow(dead_code))]
+  pub(crate) fn kinds(&self) -> &[ClipboardKind] {
+    &self.kinds
+  }
+
+  /// Returns the extractor configured with the options of the listener, for the backends that read a clipboard themselves.
+  #[cfg_attr(not(any(test, feature = "testing")), allow(dead_code))]
+  pub(crate) fn extractor(&self) -> Extractor {
+    self.extractor.clone()
+  }
+
+  /// Checks whether the changes made by an application are skipped, as set with
+  /// [`ignore_apps`](crate::ClipboardEventListenerBuilder::ignore_apps).
+  #[cfg_attr(not(all(unix, not(target_os = "macos"))), allow(dead_code))]
+  pub(crate) fn is_ignored(&self, app: &SourceApp) -> bool {
+    self.ignored_apps.iter().any(|ignored| app.matches(ignored))
+  }
+
+  pub(crate) fn stop_flag(&self) -> Arc<AtomicBool> {
+    self.stop.clone()
+  }
+
+  /// Checks whether the listener has been dropped. Backends should stop monitoring the clipboard as soon as this returns `true`.
+  pub fn is_stopped(&self) -> bool {
+    self.stop.load(Ordering::Relaxed)
+  }
+
+  /// Returns the polling interval set for the listener, which defaults to 200 milliseconds.
+  pub fn interval(&self) -> Duration {
+    self.interval
+  }
+
+  /// Sends a clipboard change to the streams.
+  ///
+  /// The item is read like the content of the system clipboard, so it goes through the same options
+  /// (such as the [`formats`](crate::ClipboardEventListenerBuilder::formats), the size limits and
+  /// [`report_skipped`](crate::ClipboardEventListenerBuilder::report_skipped)). It is discarded if its kind was
+  /// not selected with [`kinds`](crate::ClipboardEventListenerBuilder::kinds), and images are delivered as they are.
+  pub fn send(&self, kind: ClipboardKind, body: Body) {
+    if !self.kinds.contains(&kind) {
+      debug!("Found content for an unmonitored clipboard, skipping it...");
+      return;
+    }
+
+    match self.extractor.read_body(body) {
+      Ok(Some(content)) => self.send_event(ClipboardEvent::new(kind, content)),
+      Err(e) => {
+        error!("{e}");
+        self.send_error(e);
+      }
+      // Found content but ignored it (empty, beyond allowed size or with a disabled format)
+      Ok(None) => {}
+    }
+  }
+
+  /// Sends an error to the streams.
+  pub fn send_error(&self, error: ClipboardError) {
+    self.body_senders.send_all(Err(error));
+  }
+
+  /// Sends an event that was already checked against the options of the listener.
+  pub(crate) fn send_event(&self, mut event: ClipboardEvent) {
+    event.sequence = self.sequence.fetch_add(1, Ordering::Relaxed);
+
+    // Secrets are handled first, so that nothing else sees them
+    if self.secrets.is_enabled() {
+      (event.content, event.secrets) = self.secrets.apply(event.content);
+    }
+
+    if self.classifiers.is_enabled() {
+      event.classification = self.classifiers.classify(&event.content);
+    }
+
+    self.body_senders.send_all(Ok(event));
+  }
+
+  /// Sends a change made by the listener itself, unless the listener ignores them.
+  #[cfg_attr(not(all(unix, not(target_os = "macos"))), allow(dead_code))]
+  pub(crate) fn send_own_write(&self, kind: ClipboardKind, body: Body) {
+    if self.ignore_own_writes || !self.kinds.contains(&kind) {
+      return;
+    }
+
+    self.send_event(ClipboardEvent {
+      own_write: true,
+      ..ClipboardEvent::new(kind, self.extractor.ready_content(body))
+    });
+  }
+
+  /// Returns the next request sent by the listener, if there is one.
+  pub(crate) fn next_request(&self) -> Option<Request> {
+    self.requests.lock().unwrap().try_recv().ok()
+  }
+}
+
+/// An item received by the sink, which is read like the content of a system clipboard.
+pub(crate) struct BodyReader {
+  pub(crate) body: Body,
+  pub(crate) concealed: bool,
+}
+
+/// Checks the size of the data of a format, like the system clipboards do.
+fn check_size(size: usize, max_size: Option<usize>) -> Result<(), ExtractionError> {
+  if size == 0 {
+    Err(ExtractionError::EmptyContent)
+  } else if max_size.is_some_and(|max| size > max) {
+    Err(ExtractionError::SizeTooLarge(Some(size)))
+  } else {
+    Ok(())
+  }
+}
+
+impl FormatReader for BodyReader {
+  fn custom_format(
+    &mut self,
+    name: &str,
+    max_size: Option<usize>,
+  ) -> Result<Option<Vec<u8>>, ExtractionError> {
+    match &self.body {
+      Body::Custom { name: format, data } if format.as_ref() == name => {
+        check_size(data.len(), max_size)?;
+        Ok(Some(data.to_vec()))
+      }
+      _ => Ok(None),
+    }
+  }
+
+  fn image(&mut self, max_size: Option<usize>) -> Result<Option<SourceImage>, ExtractionError> {
+    match &self.body {
+      Body::Image(image) => {
+        check_size(image.bytes.len(), max_size)?;
+
+        Ok(Some(SourceImage::Processed(image.clone())))
+      }
+      _ => Ok(None),
+    }
+  }
+
+  fn files_list(
+    &mut self,
+    _max_size: Option<usize>,
+  ) -> Result<Option<Vec<PathBuf>>, ExtractionError> {
+    match &self.body {
+      Body::FileList(files) if files.is_empty() => Err(ExtractionError::EmptyContent),
+      Body::FileList(files) => Ok(Some(files.iter().map(|file| file.path.clone()).collect())),
+      // An image copied from a file comes with its path
+      Body::Image(image) => Ok(image.path.clone().map(|path| vec![path])),
+      _ => Ok(None),
+    }
+  }
+
+  fn uri_list(&mut self, _max_size: Option<usize>) -> Result<Option<Vec<Url>>, ExtractionError> {
+    match &self.body {
+      Body::UriList(uris) if uris.is_empty() => Err(ExtractionError::EmptyContent),
+      Body::UriList(uris) => Ok(Some(uris.clone())),
+      _ => Ok(None),
+    }
+  }
+
+  fn html(&mut self) -> Result<Option<HtmlContent>, ExtractionError> {
+    match &self.body {
+      Body::Html(html) => {
+        check_size(html.markup().len(), None)?;
+        Ok(Some(html.clone()))
+      }
+      _ => Ok(None),
+    }
+  }
+
+  fn rtf(&mut self) -> Result<Option<String>, ExtractionError> {
+    match &self.body {
+      Body::Rtf(rtf) => {
+        check_size(rtf.len(), None)?;
+        Ok(Some(rtf.clone()))
+      }
+      _ => Ok(None),
+    }
+  }
+
+  fn plain_text(&mut self) -> Result<Option<String>, ExtractionError> {
+    match &self.body {
+      Body::PlainText(text) => {
+        check_size(text.len(), None)?;
+        Ok(Some(text.clone()))
+      }
+      _ => Ok(None),
+    }
+  }
+
+  fn probe(&mut self, kind: FormatKind, name: Option<&str>) -> Result<Probe, ExtractionError> {
+    let size = match &self.body {
+      Body::Custom { name: format, .. } if name != Some(format.as_ref()) => {
+        return Ok(Probe::Missing);
+      }
+      Body::Custom { data, .. } => Some(data.len()),
+      Body::Image(image) => Some(image.bytes.len()),
+      Body::PlainText(text) | Body::Rtf(text) => Some(text.len()),
+      Body::Html(html) => Some(html.markup().len()),
+      Body::FileList(_) | Body::UriList(_) => None,
+    };
+
+    if kind == self.body.format_kind() {
+      Ok(Probe::Available { size })
+    } else {
+      Ok(Probe::Missing)
+    }
+  }
+
+  fn is_concealed(&mut self) -> bool {
+    self.concealed
+  }
+}
@@ -3,7 +3,7 @@ use std::{
   task::{Context, Poll},
 };
 
-use futures::{channel::mpsc::Receiver, Stream};
+use futures::{Stream, channel::mpsc::Receiver};
 
 use crate::{body::BodySendersDropHandle, error::ClipboardResult};
 
@@ -13,12 +13,12 @@ use crate::{body::BodySendersDropHandle, error::ClipboardResult};
 ///
 /// # Example
 /// ```
-/// # use clipboard_stream::{Body, ClipboardStream};
+/// # use clipboard_watcher::{Body, ClipboardStream};
 /// # use futures::stream::StreamExt;
 /// # async fn stream(mut stream: ClipboardStream) {
 /// // stream: ClipboardStream
-/// while let Some(body) = stream.next().await {
-///     if let Body::Utf8String(text) = body {
+/// while let Some(Ok(event)) = stream.next().await {
+///     if let Some(Body::PlainText(text)) = event.body() {
 ///         println!("{}", text);
 ///     }
 /// }
@@ -0,0 +1,373 @@
+//! Content extraction for the clipboards that describe their content with a list of named targets,
+//! such as the X11 selections (where targets are atoms) or the Wayland data offers (where they are MIME types).
+
+use std::path::PathBuf;
+
+use bytes::Bytes;
+
+use log::debug;
+use url::Url;
+
+use crate::{
+  body::*,
+  error::ExtractionError,
+  extraction::{FormatReader, Probe},
+  image::*,
+  text::{TextDecoder, TextDecoding, TextEncoding},
+};
+
+/// Image targets, in order of preference.
+const IMAGE_TARGETS: [&str; 6] = [
+  "image/png",
+  "image/jpeg",
+  "image/bmp",
+  "image/gif",
+  "image/webp",
+  "image/tiff",
+];
+
+const FILE_LIST_TARGETS: [&str; 2] = ["text/uri-list", "x-special/gnome-copied-files"];
+
+const HTML_TARGETS: [&str; 1] = ["text/html"];
+
+/// Targets that hold the URL of the page that some HTML was copied from, set by Chromium and Firefox.
+const SOURCE_URL_TARGETS: [&str; 2] = ["chromium/x-source-url", "text/x-moz-url-priv"];
+
+const RTF_TARGETS: [&str; 3] = ["text/rtf", "application/rtf", "text/richtext"];
+
+/// Text targets, in order of preference. The legacy ones are decoded according to their encoding (see [`text_encoding`]).
+///
+/// Only the first three hold UTF-8, so they are the only ones offered when writing to the clipboard.
+const TEXT_TARGETS: [&str; 6] = [
+  "text/plain;charset=utf-8",
+  "UTF8_STRING",
+  "text/plain",
+  "text/plain;charset=utf-16",
+  "COMPOUND_TEXT",
+  "STRING",
+];
+
+/// The target set by password managers (such as KeePassXC) on secrets, with the value `secret`.
+const PASSWORD_MANAGER_HINT: &str = "x-kde-passwordManagerHint";
+
+/// Returns the encoding of the text held by a target.
+fn text_encoding(target: &str) -> TextEncoding {
+  match target {
+    "text/plain;charset=utf-16" | "text/x-moz-url-priv" => TextEncoding::Utf16,
+    "COMPOUND_TEXT" => TextEncoding::CompoundText,
+    "STRING" => TextEncoding::Latin1,
+    _ => TextEncoding::Utf8,
+  }
+}
+
+/// A clipboard that offers its content through a list of named targets.
+pub(crate) trait TargetReader {
+  /// Returns the names of the targets offered for the current content.
+  fn targets(&mut self) -> Result<Vec<String>, ExtractionError>;
+
+  /// Reads the content for the given target.
+  ///
+  /// Returns `None` if the owner of the clipboard could not provide it.
+  fn read_target(
+    &mut self,
+    target: &str,
+    max_size: Option<usize>,
+  ) -> Result<Option<Vec<u8>>, ExtractionError>;
+}
+
+/// Parses a `text/uri-list` (or `x-special/gnome-copied-files`) payload.
+fn parse_uri_list(bytes: &[u8]) -> Vec<Url> {
+  String::from_utf8_lossy(bytes)
+    .lines()
+    .map(str::trim)
+    // Comments are allowed in uri lists, and the gnome format starts with `copy` or `cut`
+    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+    .filter_map(|line| Url::parse(line).ok())
+    .collect()
+}
+
+/// Returns the targets under which the given content is offered when writing to the clipboard, along with their data.
+pub(crate) fn body_targets(body: &Body) -> Vec<(String, Bytes)> {
+  let offer_all = |names: &[&str], data: Bytes| {
+    names
+      .iter()
+      .map(|name| (name.to_string(), data.clone()))
+      .collect()
+  };
+
+  match body {
+    Body::PlainText(text) => offer_all(&TEXT_TARGETS[..3], Bytes::copy_from_slice(text.as_bytes())),
+    Body::Html(html) => offer_all(
+      &HTML_TARGETS,
+      Bytes::copy_from_slice(html.markup().as_bytes()),
+    ),
+    Body::Rtf(rtf) => offer_all(&RTF_TARGETS[..2], Bytes::copy_from_slice(rtf.as_bytes())),
+    Body::Image(image) => offer_all(&[image.mime_type.as_str()], image.bytes.clone()),
+    Body::FileList(files) => {
+      let uris: Vec<String> = files
+        .iter()
+        .filter_map(|file| Url::from_file_path(&file.path).ok())
+        .map(String::from)
+        .collect();
+
+      vec![
+        (
+          "text/uri-list".to_string(),
+          uris.join("\r\n").into_bytes().into(),
+        ),
+        (
+          "x-special/gnome-copied-files".to_string(),
+          format!("copy\n{}", uris.join("\n")).into_bytes().into(),
+        ),
+      ]
+    }
+    Body::UriList(uris) => {
+      let uris: Vec<&str> = uris.iter().map(Url::as_str).collect();
+
+      let mut targets = offer_all(&["text/uri-list"], uris.join("\r\n").into_bytes().into());
+      // Links can also be pasted as text
+      targets.extend(offer_all(
+        &TEXT_TARGETS[..3],
+        uris.join("\n").into_bytes().into(),
+      ));
+      targets
+    }
+    Body::Custom { name, data } => offer_all(&[name.as_ref()], data.clone()),
+  }
+}
+
+/// Reads each kind of content from a [`TargetReader`], picking the most suitable target.
+pub(crate) struct TargetFormats<'a, R> {
+  reader: &'a mut R,
+  // Only queried once, when the first kind of content is read
+  targets: Option<Vec<String>>,
+  text_decoding: TextDecoding,
+}
+
+impl<'a, R: TargetReader> TargetFormats<'a, R> {
+  pub(crate) fn new(reader: &'a mut R, text_decoding: TextDecoding) -> Self {
+    TargetFormats {
+      reader,
+      targets: None,
+      text_decoding,
+    }
+  }
+
+  fn has_target(&mut self, name: &str) -> Result<bool, ExtractionError> {
+    let targets = match &mut self.targets {
+      Some(targets) => targets,
+      targets => targets.insert(self.reader.targets()?),
+    };
+
+    Ok(targets.iter().any(|target| target == name))
+  }
+
+  /// Reads the first of the given targets that is offered by the clipboard.
+  fn read_first_target(
+    &mut self,
+    names: &[&str],
+    max_size: Option<usize>,
+  ) -> Result<Option<(String, Vec<u8>)>, ExtractionError> {
+    for name in names {
+      if self.has_target(name)?
+        && let Some(bytes) = self.reader.read_target(name, max_size)?
+      {
+        return Ok(Some((name.to_string(), bytes)));
+      }
+    }
+
+    Ok(None)
+  }
+
+  fn read_string(&mut self, names: &[&str]) -> Result<Option<String>, ExtractionError> {
+    let mut decoder = TextDecoder::new(self.text_decoding);
+
+    for name in names {
+      if self.has_target(name)?
+        && let Some(bytes) = self.reader.read_target(name, None)?
+        // If the text is not valid, we try the next target
+        && let Some(text) = decoder.decode(bytes, text_encoding(name))
+      {
+        return Ok(Some(text));
+      }
+    }
+
+    decoder.finish()
+  }
+}
+
+impl<R: TargetReader> FormatReader for TargetFormats<'_, R> {
+  fn custom_format(
+    &mut self,
+    name: &str,
+    max_size: Option<usize>,
+  ) -> Result<Option<Vec<u8>>, ExtractionError> {
+    if self.has_target(name)? {
+      self.reader.read_target(name, max_size)
+    } else {
+      Ok(None)
+    }
+  }
+
+  fn image(&mut self, max_size: Option<usize>) -> Result<Option<SourceImage>, ExtractionError> {
+    match self.read_first_target(&IMAGE_TARGETS, max_size)? {
+      Some((target, bytes)) => {
+        debug!("Loaded {target} from clipboard");
+
+        // Every image target is one of the supported MIME types
+        let mime_type = MimeType::from_mime(&target).ok_or(ExtractionError::ConversionError)?;
+
+        Ok(Some(SourceImage::Encoded { bytes, mime_type }))
+      }
+      None => Ok(None),
+    }
+  }
+
+  fn files_list(
+    &mut self,
+    max_size: Option<usize>,
+  ) -> Result<Option<Vec<PathBuf>>, ExtractionError> {
+    match self.read_first_target(&FILE_LIST_TARGETS, max_size)? {
+      Some((_, bytes)) => {
+        let uris = parse_uri_list(&bytes);
+
+        if uris.is_empty() {
+          // Found list but it was empty, trigger early exit
+          return Err(ExtractionError::EmptyContent);
+        }
+
+        let files: Vec<PathBuf> = uris
+          .iter()
+          .filter(|uri| uri.scheme() == "file")
+          .filter_map(|uri| uri.to_file_path().ok())
+          .collect();
+
+        if files.is_empty() {
+          // The list only has remote locations
+          Ok(None)
+        } else {
+          debug!("Found file list");
+          Ok(Some(files))
+        }
+      }
+      None => Ok(None),
+    }
+  }
+
+  fn uri_list(&mut self, max_size: Option<usize>) -> Result<Option<Vec<Url>>, ExtractionError> {
+    match self.read_first_target(&FILE_LIST_TARGETS, max_size)? {
+      Some((_, bytes)) => {
+        let uris = parse_uri_list(&bytes);
+
+        if uris.is_empty() {
+          // Found list but it was empty, trigger early exit
+          return Err(ExtractionError::EmptyContent);
+        }
+
+        let remote: Vec<Url> = uris
+          .into_iter()
+          .filter(|uri| uri.scheme() != "file")
+          .collect();
+
+        if remote.is_empty() {
+          // The list only has local files
+          Ok(None)
+        } else {
+          debug!("Found URI list");
+          Ok(Some(remote))
+        }
+      }
+      None => Ok(None),
+    }
+  }
+
+  fn html(&mut self) -> Result<Option<HtmlContent>, ExtractionError> {
+    let Some(markup) = self.read_string(&HTML_TARGETS)? else {
+      return Ok(None);
+    };
+
+    // The source URL is optional, so failing to read it does not affect the content
+    let source_url = self
+      .read_string(&SOURCE_URL_TARGETS)
+      .ok()
+      .flatten()
+      .and_then(|text| Url::parse(text.lines().next()?.trim()).ok());
+
+    Ok(Some(HtmlContent::from_markup(markup, source_url)))
+  }
+
+  fn rtf(&mut self) -> Result<Option<String>, ExtractionError> {
+    self.read_string(&RTF_TARGETS)
+  }
+
+  fn plain_text(&mut self) -> Result<Option<String>, ExtractionError> {
+    self.read_string(&TEXT_TARGETS)
+  }
+
+  fn probe(&mut self, kind: FormatKind, name: Option<&str>) -> Result<Probe, ExtractionError> {
+    let names: &[&str] = match (kind, name) {
+      (FormatKind::Custom, Some(name)) => &[name],
+      (FormatKind::Custom, None) => &[],
+      (FormatKind::Image, _) => &IMAGE_TARGETS,
+      // The same targets hold both local files and other locations, which are only told apart once read
+      (FormatKind::FileList | FormatKind::UriList, _) => &FILE_LIST_TARGETS,
+      (FormatKind::Html, _) => &HTML_TARGETS,
+      (FormatKind::Rtf, _) => &RTF_TARGETS,
+      (FormatKind::PlainText, _) => &TEXT_TARGETS,
+    };
+
+    for name in names {
+      if self.has_target(name)? {
+        // The size of a target is only known once it is transferred
+        return Ok(Probe::Available { size: None });
+      }
+    }
+
+    Ok(Probe::Missing)
+  }
+
+  fn is_concealed(&mut self) -> bool {
+    // If the targets cannot be listed, the extraction fails anyway
+    if !self.has_target(PASSWORD_MANAGER_HINT).unwrap_or(false) {
+      return false;
+    }
+
+    // The hint is trusted even if its value cannot be read
+    self
+      .reader
+      .read_target(PASSWORD_MANAGER_HINT, None)
+      .ok()
+      .flatten()
+      .is_none_or(|value| value.trim_ascii() == b"secret")
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn text_is_only_offered_as_utf8() {
+    let targets = body_targets(&Body::PlainText("café".to_string()));
+
+    assert!(!targets.is_empty());
+
+    for (name, data) in targets {
+      assert_eq!(text_encoding(&name), TextEncoding::Utf8, "{name}");
+      assert_eq!(data.as_ref(), "café".as_bytes());
+    }
+  }
+
+  #[test]
+  fn uri_lists_are_only_offered_as_utf8_text() {
+    let uri = Url::parse("https://example.com/é").unwrap();
+    let targets = body_targets(&Body::UriList(vec![uri]));
+
+    assert!(targets.iter().any(|(name, _)| name == "text/uri-list"));
+    assert!(
+      targets
+        .iter()
+        .all(|(name, _)| text_encoding(name) == TextEncoding::Utf8)
+    );
+  }
+}
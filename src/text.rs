@@ -0,0 +1,342 @@
+//! Decoding of the text encodings that can be found on the clipboard besides UTF-8, such as the legacy X11 ones.
+
+use log::debug;
+
+use crate::error::ExtractionError;
+
+/// How text that is not valid in its encoding is handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum TextDecoding {
+  /// Invalid sequences are replaced with `U+FFFD`, so the text is always extracted.
+  #[default]
+  Lossy,
+  /// Text that is not valid in its encoding is rejected with [`ClipboardError::InvalidText`](crate::error::ClipboardError::InvalidText).
+  Strict,
+}
+
+/// The encoding of a representation of some text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(not(all(unix, not(target_os = "macos"))), allow(dead_code))]
+pub(crate) enum TextEncoding {
+  /// UTF-8, or UTF-16 if the text starts with a byte order mark.
+  Utf8,
+  /// UTF-16, in the order given by the byte order mark, or little-endian if there is none.
+  Utf16,
+  /// ISO 8859-1, used by the X11 `STRING` target.
+  Latin1,
+  /// The ISO 2022 based encoding used by the X11 `COMPOUND_TEXT` target.
+  CompoundText,
+}
+
+const ESC: u8 = 0x1b;
+const CSI: u8 = 0x9b;
+
+fn decode_utf16(bytes: &[u8], lossy: bool) -> Option<String> {
+  let (bytes, big_endian) = match bytes {
+    [0xfe, 0xff, rest @ ..] => (rest, true),
+    [0xff, 0xfe, rest @ ..] => (rest, false),
+    // Most programs write in their native order, which is little-endian on common hardware
+    _ => (bytes, false),
+  };
+
+  if bytes.len() % 2 != 0 && !lossy {
+    return None;
+  }
+
+  let units = bytes.chunks_exact(2).map(|pair| {
+    if big_endian {
+      u16::from_be_bytes([pair[0], pair[1]])
+    } else {
+      u16::from_le_bytes([pair[0], pair[1]])
+    }
+  });
+
+  let mut text = String::with_capacity(bytes.len() / 2);
+
+  for c in char::decode_utf16(units) {
+    match c {
+      Ok(c) => text.push(c),
+      Err(_) if lossy => text.push(char::REPLACEMENT_CHARACTER),
+      Err(_) => return None,
+    }
+  }
+
+  if bytes.len() % 2 != 0 {
+    text.push(char::REPLACEMENT_CHARACTER);
+  }
+
+  Some(text)
+}
+
+/// Decodes compound text, which switches between character sets with escape sequences.
+///
+/// Only ASCII, the right half of ISO 8859-1 and UTF-8 segments are supported, which covers what is produced by modern programs.
+fn decode_compound_text(bytes: &[u8], lossy: bool) -> Option<String> {
+  let mut text = String::with_capacity(bytes.len());
+  // Whether the left and right halves hold a supported character set
+  let mut left_supported = true;
+  let mut right_supported = true;
+  let mut i = 0;
+
+  while i < bytes.len() {
+    match bytes[i] {
+      ESC => {
+        let sequence = &bytes[i + 1..];
+
+        match sequence {
+          // ASCII in the left half
+          [b'(', b'B', ..] => {
+            left_supported = true;
+            i += 3;
+          }
+          // The right half of ISO 8859-1
+          [b'-', b'A', ..] => {
+            right_supported = true;
+            i += 3;
+          }
+          // A UTF-8 segment, which lasts until the return sequence
+          [b'%', b'G', rest @ ..] => {
+            let end = rest
+              .windows(3)
+              .position(|window| window == [ESC, b'%', b'@'])
+              .unwrap_or(rest.len());
+
+            match std::str::from_utf8(&rest[..end]) {
+              Ok(segment) => text.push_str(segment),
+              Err(_) if lossy => text.push_str(&String::from_utf8_lossy(&rest[..end])),
+              Err(_) => return None,
+            }
+
+            i += 3 + end + 3;
+          }
+          // Any other character set
+          _ => {
+            let length = sequence
+              .iter()
+              .position(|b| (0x30..=0x7e).contains(b))
+              .map_or(sequence.len(), |end| end + 1);
+            let designation = &sequence[..length];
+
+            // Sets that are designated to the left half start with `(` or `$(`, the others go to the right half
+            if designation.starts_with(b"(") || designation.starts_with(b"$(") {
+              left_supported = false;
+            } else {
+              right_supported = false;
+            }
+
+            i += 1 + length;
+          }
+        }
+      }
+      // Control sequences only set the direction of the text
+      CSI => {
+        let length = bytes[i + 1..]
+          .iter()
+          .position(|b| (0x40..=0x7e).contains(b))
+          .map_or(bytes.len() - i - 1, |end| end + 1);
+
+        i += 1 + length;
+      }
+      b @ (b'\n' | b'\t') => {
+        text.push(char::from(b));
+        i += 1;
+      }
+      b @ 0x20..=0x7e if left_supported => {
+        text.push(char::from(b));
+        i += 1;
+      }
+      b @ 0xa0..=0xff if right_supported => {
+        text.push(char::from(b));
+        i += 1;
+      }
+      _ if lossy => {
+        text.push(char::REPLACEMENT_CHARACTER);
+        i += 1;
+      }
+      _ => return None,
+    }
+  }
+
+  Some(text)
+}
+
+/// Decodes text with the given encoding. If it is not valid, it returns `None`, unless `lossy` is set.
+fn decode(bytes: &[u8], encoding: TextEncoding, lossy: bool) -> Option<String> {
+  match encoding {
+    TextEncoding::Utf8 if bytes.starts_with(&[0xfe, 0xff]) || bytes.starts_with(&[0xff, 0xfe]) => {
+      decode_utf16(bytes, lossy)
+    }
+    TextEncoding::Utf8 if lossy => Some(String::from_utf8_lossy(bytes).into_owned()),
+    TextEncoding::Utf8 => String::from_utf8(bytes.to_vec()).ok(),
+    TextEncoding::Utf16 => decode_utf16(bytes, lossy),
+    // Every byte is a valid character
+    TextEncoding::Latin1 => Some(bytes.iter().copied().map(char::from).collect()),
+    TextEncoding::CompoundText => decode_compound_text(bytes, lossy),
+  }
+}
+
+/// Picks the first of several representations of the same text that is valid in its encoding.
+///
+/// If none of them is, the first one is decoded lossily or rejected, according to the [`TextDecoding`].
+#[cfg_attr(windows, allow(dead_code))]
+pub(crate) struct TextDecoder {
+  decoding: TextDecoding,
+  invalid: Option<(Vec<u8>, TextEncoding)>,
+}
+
+#[cfg_attr(windows, allow(dead_code))]
+impl TextDecoder {
+  pub(crate) fn new(decoding: TextDecoding) -> Self {
+    TextDecoder {
+      decoding,
+      invalid: None,
+    }
+  }
+
+  /// Decodes a representation, returning `None` if it is not valid in its encoding.
+  pub(crate) fn decode(&mut self, bytes: Vec<u8>, encoding: TextEncoding) -> Option<String> {
+    let text = decode(&bytes, encoding, false);
+
+    if text.is_none() {
+      debug!("Found invalid text with encoding {encoding:?}");
+      self.invalid.get_or_insert((bytes, encoding));
+    }
+
+    text
+  }
+
+  /// Returns the text of the first invalid representation, after none of them could be decoded.
+  pub(crate) fn finish(self) -> Result<Option<String>, ExtractionError> {
+    match (self.invalid, self.decoding) {
+      (Some((bytes, encoding)), TextDecoding::Lossy) => Ok(decode(&bytes, encoding, true)),
+      (Some(_), TextDecoding::Strict) => Err(ExtractionError::InvalidText),
+      (None, _) => Ok(None),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn utf16le(text: &str) -> Vec<u8> {
+    text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+  }
+
+  fn utf16be(text: &str) -> Vec<u8> {
+    text.encode_utf16().flat_map(u16::to_be_bytes).collect()
+  }
+
+  #[test]
+  fn utf16_follows_the_byte_order_mark() {
+    let little = [&[0xff, 0xfe][..], &utf16le("héllo 🎉")].concat();
+    let big = [&[0xfe, 0xff][..], &utf16be("héllo 🎉")].concat();
+
+    assert_eq!(decode_utf16(&little, false).as_deref(), Some("héllo 🎉"));
+    assert_eq!(decode_utf16(&big, false).as_deref(), Some("héllo 🎉"));
+  }
+
+  #[test]
+  fn utf16_defaults_to_little_endian() {
+    assert_eq!(decode_utf16(&utf16le("abc"), false).as_deref(), Some("abc"));
+  }
+
+  #[test]
+  fn utf16_with_odd_length() {
+    let mut bytes = utf16le("ab");
+    bytes.push(b'c');
+
+    assert_eq!(decode_utf16(&bytes, false), None);
+    assert_eq!(decode_utf16(&bytes, true).as_deref(), Some("ab\u{fffd}"));
+  }
+
+  #[test]
+  fn utf16_with_unpaired_surrogate() {
+    let bytes = [0x3d, 0xd8, b'a', 0];
+
+    assert_eq!(decode_utf16(&bytes, false), None);
+    assert_eq!(decode_utf16(&bytes, true).as_deref(), Some("\u{fffd}a"));
+  }
+
+  #[test]
+  fn utf8_with_byte_order_mark_is_utf16() {
+    let bytes = [&[0xff, 0xfe][..], &utf16le("hi")].concat();
+
+    assert_eq!(
+      decode(&bytes, TextEncoding::Utf8, false).as_deref(),
+      Some("hi")
+    );
+  }
+
+  #[test]
+  fn latin1_maps_every_byte() {
+    assert_eq!(
+      decode(b"caf\xe9", TextEncoding::Latin1, false).as_deref(),
+      Some("café")
+    );
+  }
+
+  #[test]
+  fn compound_text_with_latin1_and_utf8_segments() {
+    let bytes = b"caf\xe9 \x1b%G\xe2\x82\xac\x1b%@ \x1b(Bok";
+
+    assert_eq!(
+      decode_compound_text(bytes, false).as_deref(),
+      Some("café € ok")
+    );
+  }
+
+  #[test]
+  fn compound_text_with_unsupported_character_set() {
+    // JIS X 0208 in the left half, which is not supported
+    let bytes = b"a\x1b$(B\x30\x21\x1b(Bb";
+
+    assert_eq!(decode_compound_text(bytes, false), None);
+    assert_eq!(
+      decode_compound_text(bytes, true).as_deref(),
+      Some("a\u{fffd}\u{fffd}b")
+    );
+  }
+
+  #[test]
+  fn compound_text_skips_direction_sequences() {
+    assert_eq!(
+      decode_compound_text(b"\x9b1]ab\x9b]", false).as_deref(),
+      Some("ab")
+    );
+  }
+
+  #[test]
+  fn compound_text_with_unterminated_utf8_segment() {
+    assert_eq!(
+      decode_compound_text(b"\x1b%G\xc3\xa9t\xc3\xa9", false).as_deref(),
+      Some("été")
+    );
+  }
+
+  #[test]
+  fn decoder_picks_the_first_valid_representation() {
+    let mut decoder = TextDecoder::new(TextDecoding::Strict);
+
+    assert_eq!(decoder.decode(b"\xff".to_vec(), TextEncoding::Utf8), None);
+    assert_eq!(
+      decoder
+        .decode(b"ok".to_vec(), TextEncoding::Latin1)
+        .as_deref(),
+      Some("ok")
+    );
+  }
+
+  #[test]
+  fn decoder_falls_back_according_to_the_policy() {
+    let mut lossy = TextDecoder::new(TextDecoding::Lossy);
+    lossy.decode(b"a\xff".to_vec(), TextEncoding::Utf8);
+
+    assert_eq!(lossy.finish().ok().flatten().as_deref(), Some("a\u{fffd}"));
+
+    let mut strict = TextDecoder::new(TextDecoding::Strict);
+    strict.decode(b"a\xff".to_vec(), TextEncoding::Utf8);
+
+    assert!(matches!(strict.finish(), Err(ExtractionError::InvalidText)));
+  }
+}
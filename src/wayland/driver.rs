@@ -0,0 +1,19 @@
+use crate::{
+  Backend, driver::Driver, error::ClipboardError, extraction::Extractor, sink::ClipboardSink,
+  wayland::observer::WaylandObserver,
+};
+
+impl Driver {
+  /// Construct [`Driver`] and spawn a thread for monitoring the Wayland clipboard
+  pub(crate) fn new_wayland(
+    sink: ClipboardSink,
+    extractor: Extractor,
+  ) -> Result<Self, ClipboardError> {
+    let interval = sink.interval();
+    let kinds = sink.kinds().to_vec();
+
+    Self::spawn(Backend::WaylandDataControl, sink, move |stop| {
+      WaylandObserver::new(stop, interval, extractor, kinds)
+    })
+  }
+}
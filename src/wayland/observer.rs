@@ -0,0 +1,699 @@
+use std::{
+  collections::HashMap,
+  fs::File,
+  io::{ErrorKind, Read, Write},
+  os::fd::{AsFd, BorrowedFd, OwnedFd},
+  sync::{
+    Arc, Mutex,
+    atomic::{AtomicBool, Ordering},
+  },
+  time::Duration,
+};
+
+use bytes::Bytes;
+use log::{debug, error, info};
+use rustix::{
+  event::{PollFd, PollFlags, Timespec, poll},
+  pipe::{PipeFlags, pipe_with},
+};
+use wayland_client::{
+  Connection, Dispatch, EventQueue, Proxy, QueueHandle,
+  backend::WaylandError,
+  delegate_noop, event_created_child,
+  globals::{GlobalListContents, registry_queue_init},
+  protocol::{wl_registry::WlRegistry, wl_seat::WlSeat},
+};
+use wayland_protocols::ext::data_control::v1::client::{
+  ext_data_control_device_v1::{self, ExtDataControlDeviceV1},
+  ext_data_control_manager_v1::ExtDataControlManagerV1,
+  ext_data_control_offer_v1::{self, ExtDataControlOfferV1},
+  ext_data_control_source_v1::{self, ExtDataControlSourceV1},
+};
+use wayland_protocols_wlr::data_control::v1::client::{
+  zwlr_data_control_device_v1::{self, ZwlrDataControlDeviceV1},
+  zwlr_data_control_manager_v1::ZwlrDataControlManagerV1,
+  zwlr_data_control_offer_v1::{self, ZwlrDataControlOfferV1},
+  zwlr_data_control_source_v1::{self, ZwlrDataControlSourceV1},
+};
+
+use crate::{
+  AvailableFormat, Body, ClipboardEvent, ClipboardKind,
+  error::{ClipboardError, ExtractionError},
+  extraction::Extractor,
+  lazy::ChangeId,
+  observer::Observer,
+  sink::{ClipboardSink, Request},
+  targets::{TargetFormats, TargetReader, body_targets},
+};
+
+/// How long to wait for the owner of the clipboard to send the data.
+const TRANSFER_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The MIME types announced by a data offer.
+type MimeTypes = Mutex<Vec<String>>;
+
+fn init_error(e: impl std::fmt::Display) -> ClipboardError {
+  ClipboardError::InitializationError(e.to_string())
+}
+
+fn read_error(e: impl std::fmt::Display) -> ExtractionError {
+  ExtractionError::ReadError(e.to_string())
+}
+
+fn monitor_error(e: impl std::fmt::Display) -> ClipboardError {
+  ClipboardError::MonitorFailed(e.to_string())
+}
+
+fn write_error(e: impl std::fmt::Display) -> ClipboardError {
+  ClipboardError::WriteError(e.to_string())
+}
+
+/// A data offer from either of the supported data-control protocols, which are otherwise identical.
+enum DataOffer {
+  Ext(ExtDataControlOfferV1),
+  Wlr(ZwlrDataControlOfferV1),
+}
+
+impl DataOffer {
+  fn mime_types(&self) -> Vec<String> {
+    let mime_types = match self {
+      DataOffer::Ext(offer) => offer.data::<MimeTypes>(),
+      DataOffer::Wlr(offer) => offer.data::<MimeTypes>(),
+    };
+
+    mime_types
+      .map(|mime_types| mime_types.lock().unwrap().clone())
+      .unwrap_or_default()
+  }
+
+  fn receive(&self, mime_type: String, fd: BorrowedFd) {
+    match self {
+      DataOffer::Ext(offer) => offer.receive(mime_type, fd),
+      DataOffer::Wlr(offer) => offer.receive(mime_type, fd),
+    }
+  }
+
+  fn destroy(&self) {
+    match self {
+      DataOffer::Ext(offer) => offer.destroy(),
+      DataOffer::Wlr(offer) => offer.destroy(),
+    }
+  }
+}
+
+/// The content offered by one of our data sources.
+struct SourceData {
+  kind: ClipboardKind,
+  targets: Vec<(String, Bytes)>,
+}
+
+impl SourceData {
+  /// Sends the data for the given MIME type to another client.
+  fn send(&self, mime_type: &str, fd: OwnedFd) {
+    let Some((_, data)) = self.targets.iter().find(|(target, _)| target == mime_type) else {
+      return;
+    };
+
+    let data = data.clone();
+
+    // Writing blocks until the other client reads everything, so it must not hold up the monitoring
+    std::thread::spawn(move || {
+      if let Err(e) = File::from(fd).write_all(&data) {
+        debug!("Failed to send the clipboard content: {e}");
+      }
+    });
+  }
+}
+
+/// A data source created for a write, from either of the supported data-control protocols.
+#[derive(Clone, PartialEq)]
+enum DataSource {
+  Ext(ExtDataControlSourceV1),
+  Wlr(ZwlrDataControlSourceV1),
+}
+
+impl DataSource {
+  fn destroy(&self) {
+    match self {
+      DataSource::Ext(source) => source.destroy(),
+      DataSource::Wlr(source) => source.destroy(),
+    }
+  }
+}
+
+/// The data device, kept alive for as long as the observer is running, along with the manager used to create the data sources.
+enum DataDevice {
+  Ext(ExtDataControlManagerV1, ExtDataControlDeviceV1),
+  Wlr(ZwlrDataControlManagerV1, ZwlrDataControlDeviceV1),
+}
+
+impl DataDevice {
+  /// Creates a data source with the given targets and makes it the current selection.
+  fn set_selection(
+    &self,
+    kind: ClipboardKind,
+    targets: Vec<(String, Bytes)>,
+    qh: &QueueHandle<State>,
+  ) -> Result<DataSource, ClipboardError> {
+    let mime_types: Vec<String> = targets.iter().map(|(target, _)| target.clone()).collect();
+    let data = SourceData { kind, targets };
+
+    match self {
+      DataDevice::Ext(manager, device) => {
+        let source = manager.create_data_source(qh, data);
+
+        for mime_type in mime_types {
+          source.offer(mime_type);
+        }
+
+        match kind {
+          ClipboardKind::Clipboard => device.set_selection(Some(&source)),
+          ClipboardKind::Primary => device.set_primary_selection(Some(&source)),
+        }
+
+        Ok(DataSource::Ext(source))
+      }
+      DataDevice::Wlr(manager, device) => {
+        // The primary selection was only added in the second version
+        if kind == ClipboardKind::Primary && manager.version() < 2 {
+          return Err(ClipboardError::WriteError(
+            "the compositor does not support setting the primary selection".to_string(),
+          ));
+        }
+
+        let source = manager.create_data_source(qh, data);
+
+        for mime_type in mime_types {
+          source.offer(mime_type);
+        }
+
+        match kind {
+          ClipboardKind::Clipboard => device.set_selection(Some(&source)),
+          ClipboardKind::Primary => device.set_primary_selection(Some(&source)),
+        }
+
+        Ok(DataSource::Wlr(source))
+      }
+    }
+  }
+}
+
+impl Drop for DataDevice {
+  fn drop(&mut self) {
+    match self {
+      DataDevice::Ext(manager, device) => {
+        device.destroy();
+        manager.destroy();
+      }
+      DataDevice::Wlr(manager, device) => {
+        device.destroy();
+        manager.destroy();
+      }
+    }
+  }
+}
+
+struct State {
+  kinds: Vec<ClipboardKind>,
+  selections: HashMap<ClipboardKind, DataOffer>,
+  // The selections that changed since the last dispatch, in order
+  changed: Vec<ClipboardKind>,
+  // The sources of our writes, for as long as they hold the selection
+  sources: HashMap<ClipboardKind, DataSource>,
+  finished: bool,
+}
+
+impl State {
+  fn cancel_source(&mut self, kind: ClipboardKind, source: DataSource) {
+    if self.sources.get(&kind) == Some(&source) {
+      self.sources.remove(&kind);
+    }
+
+    source.destroy();
+  }
+
+  fn set_selection(&mut self, kind: ClipboardKind, offer: Option<DataOffer>) {
+    if !self.kinds.contains(&kind) {
+      if let Some(offer) = offer {
+        offer.destroy();
+      }
+      return;
+    }
+
+    if let Some(previous) = self.selections.remove(&kind) {
+      previous.destroy();
+    }
+
+    if let Some(offer) = offer {
+      self.selections.insert(kind, offer);
+    }
+
+    if !self.changed.contains(&kind) {
+      self.changed.push(kind);
+    }
+  }
+}
+
+pub(super) struct WaylandObserver {
+  stop: Arc<AtomicBool>,
+  conn: Connection,
+  queue: EventQueue<State>,
+  state: State,
+  device: DataDevice,
+  interval: Duration,
+  extractor: Extractor,
+}
+
+impl WaylandObserver {
+  pub(super) fn new(
+    stop: Arc<AtomicBool>,
+    interval: Duration,
+    extractor: Extractor,
+    kinds: Vec<ClipboardKind>,
+  ) -> Result<Self, ClipboardError> {
+    let conn = Connection::connect_to_env().map_err(init_error)?;
+
+    let (globals, mut queue) = registry_queue_init::<State>(&conn).map_err(init_error)?;
+    let qh = queue.handle();
+
+    let seat: WlSeat = globals.bind(&qh, 1..=1, ()).map_err(init_error)?;
+
+    // The ext protocol is the standardized version of the wlr one, so we prefer it when available
+    let device = if let Ok(manager) = globals.bind::<ExtDataControlManagerV1, _, _>(&qh, 1..=1, ())
+    {
+      debug!("Using the ext-data-control protocol");
+      let device = manager.get_data_device(&seat, &qh, ());
+      DataDevice::Ext(manager, device)
+    } else if let Ok(manager) = globals.bind::<ZwlrDataControlManagerV1, _, _>(&qh, 1..=2, ()) {
+      debug!("Using the wlr-data-control protocol");
+
+      // The primary selection was only added in the second version
+      if manager.version() < 2 && kinds.contains(&ClipboardKind::Primary) {
+        return Err(ClipboardError::InitializationError(
+          "the compositor does not support monitoring the primary selection".to_string(),
+        ));
+      }
+
+      let device = manager.get_data_device(&seat, &qh, ());
+      DataDevice::Wlr(manager, device)
+    } else {
+      return Err(ClipboardError::InitializationError(
+        "the compositor does not support the data-control protocol".to_string(),
+      ));
+    };
+
+    let mut state = State {
+      kinds,
+      selections: HashMap::new(),
+      changed: Vec::new(),
+      sources: HashMap::new(),
+      finished: false,
+    };
+
+    // The compositor sends the current selections as soon as the device is created,
+    // but those are not changes, so we don't report them
+    queue.roundtrip(&mut state).map_err(init_error)?;
+    state.changed.clear();
+
+    Ok(WaylandObserver {
+      stop,
+      conn,
+      queue,
+      state,
+      device,
+      interval,
+      extractor,
+    })
+  }
+
+  /// Waits for new events (for at most the length of the interval) and dispatches them.
+  fn dispatch(&mut self) -> Result<(), ClipboardError> {
+    self
+      .queue
+      .dispatch_pending(&mut self.state)
+      .map_err(monitor_error)?;
+    self.queue.flush().map_err(monitor_error)?;
+
+    if let Some(guard) = self.queue.prepare_read() {
+      let timeout = Timespec {
+        tv_sec: self.interval.as_secs() as _,
+        tv_nsec: self.interval.subsec_nanos() as _,
+      };
+      let mut fds = [PollFd::from_borrowed_fd(
+        guard.connection_fd(),
+        PollFlags::IN,
+      )];
+
+      if poll(&mut fds, Some(&timeout)).map_err(monitor_error)? > 0 {
+        match guard.read() {
+          Ok(_) => {}
+          Err(WaylandError::Io(e)) if e.kind() == ErrorKind::WouldBlock => {}
+          Err(e) => return Err(monitor_error(e)),
+        }
+      }
+    }
+
+    self
+      .queue
+      .dispatch_pending(&mut self.state)
+      .map_err(monitor_error)?;
+
+    if self.state.finished {
+      return Err(ClipboardError::MonitorFailed(
+        "the data device is no longer valid".to_string(),
+      ));
+    }
+
+    Ok(())
+  }
+
+  fn handle_request(&mut self, request: Request, sink: &ClipboardSink) {
+    match request {
+      Request::Set { kind, body, reply } => {
+        let result = self.set_selection(kind, body_targets(&body));
+
+        if result.is_ok() {
+          self.extractor.invalidate(kind);
+          sink.send_own_write(kind, body);
+        }
+
+        let _ = reply.send(result);
+      }
+      Request::Fetch {
+        change,
+        format,
+        reply,
+      } => {
+        let _ = reply.send(self.fetch(&change, &format));
+      }
+    }
+  }
+
+  /// Reads the content of a lazy change from the current selection.
+  fn fetch(&mut self, change: &ChangeId, format: &AvailableFormat) -> Result<Body, ClipboardError> {
+    // The events that were already received may contain a change that was not reported yet
+    self
+      .queue
+      .dispatch_pending(&mut self.state)
+      .map_err(monitor_error)?;
+
+    let offer = match self.state.selections.get(&change.kind) {
+      Some(offer) if !self.state.changed.contains(&change.kind) => offer,
+      _ => return Err(ClipboardError::ContentChanged),
+    };
+
+    let text_decoding = self.extractor.text_decoding();
+    let mut reader = OfferReader {
+      conn: &self.conn,
+      offer,
+    };
+
+    self.extractor.fetch(
+      change,
+      format,
+      &mut TargetFormats::new(&mut reader, text_decoding),
+    )
+  }
+
+  fn set_selection(
+    &mut self,
+    kind: ClipboardKind,
+    targets: Vec<(String, Bytes)>,
+  ) -> Result<(), ClipboardError> {
+    let source = self
+      .device
+      .set_selection(kind, targets, &self.queue.handle())?;
+
+    if let Some(previous) = self.state.sources.insert(kind, source.clone()) {
+      previous.destroy();
+    }
+
+    // The compositor answers with a selection event for our own source, which must not be read,
+    // since we would be waiting for ourselves to send the data
+    self.queue.roundtrip(&mut self.state).map_err(write_error)?;
+
+    // If the source is still there, nobody replaced it in the meantime, so the current selection is ours
+    if self.state.sources.get(&kind) == Some(&source) {
+      self.state.changed.retain(|changed| *changed != kind);
+    }
+
+    Ok(())
+  }
+}
+
+impl Observer for WaylandObserver {
+  fn observe(&mut self, sink: ClipboardSink) {
+    info!("Started monitoring the clipboard");
+
+    while !self.stop.load(Ordering::Relaxed) {
+      while let Some(request) = sink.next_request() {
+        self.handle_request(request, &sink);
+      }
+
+      if let Err(error) = self.dispatch() {
+        error!("{error}");
+
+        sink.send_error(error);
+
+        error!("Fatal error, terminating clipboard watcher");
+        break;
+      }
+
+      for kind in std::mem::take(&mut self.state.changed) {
+        let Some(offer) = self.state.selections.get(&kind) else {
+          debug!("The {kind:?} selection was cleared");
+          continue;
+        };
+
+        let text_decoding = self.extractor.text_decoding();
+        let mut reader = OfferReader {
+          conn: &self.conn,
+          offer,
+        };
+
+        match self
+          .extractor
+          .get_clipboard_content(kind, &mut TargetFormats::new(&mut reader, text_decoding))
+        {
+          Ok(Some(content)) => sink.send_event(ClipboardEvent::new(kind, content)),
+          Err(e) => {
+            error!("{e}");
+            sink.send_error(e);
+          }
+          // Found content but ignored it (empty or beyond allowed size)
+          Ok(None) => {}
+        }
+      }
+    }
+  }
+}
+
+/// Reads the data sent through a pipe by the owner of the clipboard.
+///
+/// Returns `None` if the owner does not send anything in time.
+fn read_pipe(fd: OwnedFd, max_size: Option<usize>) -> Result<Option<Vec<u8>>, ExtractionError> {
+  let timeout = Timespec {
+    tv_sec: TRANSFER_TIMEOUT.as_secs() as _,
+    tv_nsec: TRANSFER_TIMEOUT.subsec_nanos() as _,
+  };
+
+  let mut file = File::from(fd);
+  let mut data = Vec::new();
+  let mut buffer = [0; 8192];
+
+  loop {
+    let mut fds = [PollFd::new(&file, PollFlags::IN)];
+
+    if poll(&mut fds, Some(&timeout)).map_err(read_error)? == 0 {
+      debug!("Timed out while waiting for the clipboard owner");
+      return Ok(None);
+    }
+
+    match file.read(&mut buffer) {
+      // The owner closes the pipe once everything has been sent
+      Ok(0) => break,
+      Ok(n) => {
+        data.extend_from_slice(&buffer[..n]);
+
+        if max_size.is_some_and(|max| data.len() > max) {
+          return Err(ExtractionError::SizeTooLarge(Some(data.len())));
+        }
+      }
+      Err(e) if e.kind() == ErrorKind::Interrupted => {}
+      Err(e) => return Err(read_error(e)),
+    }
+  }
+
+  if data.is_empty() {
+    // Found content but it was empty, trigger early exit
+    Err(ExtractionError::EmptyContent)
+  } else {
+    Ok(Some(data))
+  }
+}
+
+/// Reads the content of the current selection.
+struct OfferReader<'a> {
+  conn: &'a Connection,
+  offer: &'a DataOffer,
+}
+
+impl TargetReader for OfferReader<'_> {
+  fn targets(&mut self) -> Result<Vec<String>, ExtractionError> {
+    Ok(self.offer.mime_types())
+  }
+
+  fn read_target(
+    &mut self,
+    target: &str,
+    max_size: Option<usize>,
+  ) -> Result<Option<Vec<u8>>, ExtractionError> {
+    let (read, write) = pipe_with(PipeFlags::CLOEXEC).map_err(read_error)?;
+
+    self.offer.receive(target.to_string(), write.as_fd());
+    self.conn.flush().map_err(read_error)?;
+
+    // We must close our copy of the write end, or we would never see the end of the transfer
+    drop(write);
+
+    read_pipe(read, max_size)
+  }
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for State {
+  fn event(
+    _: &mut Self,
+    _: &WlRegistry,
+    _: <WlRegistry as Proxy>::Event,
+    _: &GlobalListContents,
+    _: &Connection,
+    _: &QueueHandle<Self>,
+  ) {
+  }
+}
+
+delegate_noop!(State: ignore WlSeat);
+delegate_noop!(State: ExtDataControlManagerV1);
+delegate_noop!(State: ZwlrDataControlManagerV1);
+
+impl Dispatch<ExtDataControlDeviceV1, ()> for State {
+  fn event(
+    state: &mut Self,
+    _: &ExtDataControlDeviceV1,
+    event: ext_data_control_device_v1::Event,
+    _: &(),
+    _: &Connection,
+    _: &QueueHandle<Self>,
+  ) {
+    match event {
+      ext_data_control_device_v1::Event::Selection { id } => {
+        state.set_selection(ClipboardKind::Clipboard, id.map(DataOffer::Ext))
+      }
+      ext_data_control_device_v1::Event::PrimarySelection { id } => {
+        state.set_selection(ClipboardKind::Primary, id.map(DataOffer::Ext))
+      }
+      ext_data_control_device_v1::Event::Finished => state.finished = true,
+      _ => {}
+    }
+  }
+
+  event_created_child!(State, ExtDataControlDeviceV1, [
+    ext_data_control_device_v1::EVT_DATA_OFFER_OPCODE => (ExtDataControlOfferV1, MimeTypes::default()),
+  ]);
+}
+
+impl Dispatch<ZwlrDataControlDeviceV1, ()> for State {
+  fn event(
+    state: &mut Self,
+    _: &ZwlrDataControlDeviceV1,
+    event: zwlr_data_control_device_v1::Event,
+    _: &(),
+    _: &Connection,
+    _: &QueueHandle<Self>,
+  ) {
+    match event {
+      zwlr_data_control_device_v1::Event::Selection { id } => {
+        state.set_selection(ClipboardKind::Clipboard, id.map(DataOffer::Wlr))
+      }
+      zwlr_data_control_device_v1::Event::PrimarySelection { id } => {
+        state.set_selection(ClipboardKind::Primary, id.map(DataOffer::Wlr))
+      }
+      zwlr_data_control_device_v1::Event::Finished => state.finished = true,
+      _ => {}
+    }
+  }
+
+  event_created_child!(State, ZwlrDataControlDeviceV1, [
+    zwlr_data_control_device_v1::EVT_DATA_OFFER_OPCODE => (ZwlrDataControlOfferV1, MimeTypes::default()),
+  ]);
+}
+
+impl Dispatch<ExtDataControlSourceV1, SourceData> for State {
+  fn event(
+    state: &mut Self,
+    source: &ExtDataControlSourceV1,
+    event: ext_data_control_source_v1::Event,
+    data: &SourceData,
+    _: &Connection,
+    _: &QueueHandle<Self>,
+  ) {
+    match event {
+      ext_data_control_source_v1::Event::Send { mime_type, fd } => data.send(&mime_type, fd),
+      // Another client took the selection
+      ext_data_control_source_v1::Event::Cancelled => {
+        state.cancel_source(data.kind, DataSource::Ext(source.clone()))
+      }
+      _ => {}
+    }
+  }
+}
+
+impl Dispatch<ZwlrDataControlSourceV1, SourceData> for State {
+  fn event(
+    state: &mut Self,
+    source: &ZwlrDataControlSourceV1,
+    event: zwlr_data_control_source_v1::Event,
+    data: &SourceData,
+    _: &Connection,
+    _: &QueueHandle<Self>,
+  ) {
+    match event {
+      zwlr_data_control_source_v1::Event::Send { mime_type, fd } => data.send(&mime_type, fd),
+      // Another client took the selection
+      zwlr_data_control_source_v1::Event::Cancelled => {
+        state.cancel_source(data.kind, DataSource::Wlr(source.clone()))
+      }
+      _ => {}
+    }
+  }
+}
+
+impl Dispatch<ExtDataControlOfferV1, MimeTypes> for State {
+  fn event(
+    _: &mut Self,
+    _: &ExtDataControlOfferV1,
+    event: ext_data_control_offer_v1::Event,
+    mime_types: &MimeTypes,
+    _: &Connection,
+    _: &QueueHandle<Self>,
+  ) {
+    if let ext_data_control_offer_v1::Event::Offer { mime_type } = event {
+      mime_types.lock().unwrap().push(mime_type);
+    }
+  }
+}
+
+impl Dispatch<ZwlrDataControlOfferV1, MimeTypes> for State {
+  fn event(
+    _: &mut Self,
+    _: &ZwlrDataControlOfferV1,
+    event: zwlr_data_control_offer_v1::Event,
+    mime_types: &MimeTypes,
+    _: &Connection,
+    _: &QueueHandle<Self>,
+  ) {
+    if let zwlr_data_control_offer_v1::Event::Offer { mime_type } = event {
+      mime_types.lock().unwrap().push(mime_type);
+    }
+  }
+}
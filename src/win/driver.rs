@@ -1,31 +1,21 @@
 use crate::observer::Observer;
 use crate::win::observer::WinObserver;
-use std::sync::{Arc, atomic::AtomicBool};
-use std::time::Duration;
-
-use crate::{body::BodySenders, driver::Driver, error::ClipboardError};
+use crate::{
+  Backend, driver::Driver, error::ClipboardError, extraction::Extractor, sink::ClipboardSink,
+};
 
 impl Driver {
   /// Construct [`Driver`] and spawn a thread for monitoring clipboard events
-  pub(crate) fn new(
-    body_senders: Arc<BodySenders>,
-    interval: Option<Duration>,
-    custom_formats: Vec<impl AsRef<str>>,
-    max_image_bytes: Option<usize>,
-    max_bytes: Option<usize>,
-  ) -> Result<Self, ClipboardError> {
+  pub(crate) fn new(sink: ClipboardSink, extractor: Extractor) -> Result<Self, ClipboardError> {
     use std::sync::mpsc;
 
-    let stop = Arc::new(AtomicBool::new(false));
+    let stop = sink.stop_flag();
 
     let stop_cl = stop.clone();
 
     let (init_tx, init_rx) = mpsc::sync_channel(0);
 
-    let thread_safe_formats_list: Vec<Arc<str>> = custom_formats
-      .into_iter()
-      .map(|f| f.as_ref().into())
-      .collect();
+    let interval = sink.interval();
 
     // spawn OS thread
     // observe clipboard change event and send item
@@ -34,17 +24,10 @@ impl Driver {
         Ok(monitor) => {
           init_tx.send(Ok(())).unwrap();
 
-          let mut observer = WinObserver::new(
-            stop_cl,
-            monitor,
-            thread_safe_formats_list,
-            interval,
-            max_image_bytes,
-            max_bytes,
-          );
+          let mut observer = WinObserver::new(stop_cl, monitor, interval, extractor);
 
           // event change observe loop
-          observer.observe(body_senders);
+          observer.observe(sink);
         }
         Err(e) => {
           init_tx.send(Err(e)).unwrap();
@@ -57,6 +40,7 @@ impl Driver {
       Ok(Ok(())) => Ok(Driver {
         stop,
         handle: Some(handle),
+        backend: Backend::Windows,
       }),
       Ok(Err(e)) => Err(ClipboardError::InitializationError(format!("{e:#?}"))),
       Err(e) => Err(ClipboardError::TryRecvError(e.to_string())),
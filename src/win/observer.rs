@@ -1,16 +1,28 @@
-use std::sync::{
-  atomic::{AtomicBool, Ordering},
-  Arc,
+use std::{
+  collections::HashMap,
+  num::{NonZeroU32, NonZeroUsize},
+  path::PathBuf,
+  sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+  },
+  time::Duration,
 };
 
-use clipboard_win::{formats, Clipboard, Getter};
+use clipboard_win::{Clipboard, Getter, formats};
 use log::{debug, error, info};
+use url::Url;
 
 use crate::{
-  body::{BodySenders, ClipboardImage},
+  AvailableFormat, Backend, Body, FormatKind, HtmlContent, MimeType,
   error::{ClipboardError, ExtractionError},
+  event::{ClipboardContent, ClipboardEvent, ClipboardKind},
+  extraction::{Extractor, FormatReader, Probe},
+  html::parse_cf_html,
+  image::SourceImage,
+  lazy::ChangeId,
   observer::Observer,
-  Body,
+  sink::{ClipboardSink, Request},
 };
 
 pub(super) struct WinObserver {
@@ -18,17 +30,22 @@ pub(super) struct WinObserver {
   monitor: clipboard_win::Monitor,
   html_format: Option<clipboard_win::formats::Html>,
   png_format: Option<NonZeroU32>,
+  rtf_format: Option<NonZeroU32>,
+  url_format: Option<NonZeroU32>,
+  // Set by password managers on secrets
+  exclude_format: Option<NonZeroU32>,
   custom_formats: HashMap<Arc<str>, NonZeroU32>,
   interval: Duration,
-  max_image_size: Option<usize>,
-  max_size: Option<usize>,
+  extractor: Extractor,
+  // The sequence number of the clipboard at the latest change, to detect changes that were not reported yet
+  change_seq: Option<NonZeroU32>,
 }
 
-struct FormatTooLarge;
+struct FormatTooLarge(usize);
 
 impl From<FormatTooLarge> for ExtractionError {
-  fn from(_: FormatTooLarge) -> Self {
-    Self::SizeTooLarge
+  fn from(FormatTooLarge(size): FormatTooLarge) -> Self {
+    Self::SizeTooLarge(Some(size))
   }
 }
 
@@ -38,11 +55,11 @@ fn format_is_valid(format_id: u32, max_bytes: Option<usize>) -> Result<bool, For
   match max_bytes {
     Some(max) => match clipboard_win::size(format_id) {
       Some(size) => {
-        if max > size.get() {
+        if size.get() <= max {
           Ok(true)
         } else {
           // Invalid side, we use an error to exit early later on
-          Err(FormatTooLarge)
+          Err(FormatTooLarge(size.get()))
         }
       }
       // Format is not present at all
@@ -57,19 +74,22 @@ impl WinObserver {
   pub(super) fn new(
     stop: Arc<AtomicBool>,
     monitor: clipboard_win::Monitor,
-    custom_formats: Vec<Arc<str>>,
-    interval: Option<Duration>,
-    max_image_bytes: Option<usize>,
-    max_bytes: Option<usize>,
+    interval: Duration,
+    extractor: Extractor,
   ) -> Self {
     let html_format = clipboard_win::formats::Html::new();
     let png_format = clipboard_win::register_format("PNG");
-
-    let custom_formats_map: HashMap<Arc<str>, NonZeroU32> = custom_formats
-      .into_iter()
+    let rtf_format = clipboard_win::register_format("Rich Text Format");
+    let url_format = clipboard_win::register_format("UniformResourceLocatorW");
+    let exclude_format =
+      clipboard_win::register_format("ExcludeClipboardContentFromMonitorProcessing");
+
+    let custom_formats_map: HashMap<Arc<str>, NonZeroU32> = extractor
+      .custom_formats()
+      .iter()
       .filter_map(|name| {
         if let Some(id) = clipboard_win::register_format(name.as_ref()) {
-          Some((name, id))
+          Some((name.clone(), id))
         } else {
           log::error!("Failed to register custom clipboard type `{name}`");
           None
@@ -77,23 +97,18 @@ impl WinObserver {
       })
       .collect();
 
-    let max_image_bytes = if max_image_bytes.is_none() && max_bytes.is_some() {
-      debug!("Using global size limit for images...");
-
-      max_bytes
-    } else {
-      max_image_bytes
-    };
-
     WinObserver {
       stop,
       monitor,
       html_format,
       png_format,
+      rtf_format,
+      url_format,
+      exclude_format,
       custom_formats: custom_formats_map,
-      interval: interval.unwrap_or_else(|| Duration::from_millis(200)),
-      max_image_size: max_image_bytes,
-      max_size: max_bytes,
+      interval,
+      extractor,
+      change_seq: None,
     }
   }
 
@@ -112,37 +127,124 @@ impl WinObserver {
     }
   }
 
-  pub(super) fn extract_image_bytes(&self) -> Result<Option<Vec<u8>>, ExtractionError> {
-    use clipboard_win::formats;
+  pub(super) fn get_clipboard_content(
+    &mut self,
+  ) -> Result<Option<ClipboardContent>, ClipboardError> {
+    let _clipboard =
+      Clipboard::new_attempts(10).map_err(|e| ClipboardError::ReadError(e.to_string()))?;
+
+    self.change_seq = clipboard_win::seq_num();
+
+    self.extractor.get_clipboard_content(
+      ClipboardKind::Clipboard,
+      &mut WinFormats {
+        html_format: self.html_format,
+        png_format: self.png_format,
+        rtf_format: self.rtf_format,
+        url_format: self.url_format,
+        exclude_format: self.exclude_format,
+        custom_formats: &self.custom_formats,
+      },
+    )
+  }
+
+  fn handle_request(&mut self, request: Request) {
+    match request {
+      // Writing is refused by the listener, so this is never reached
+      Request::Set { reply, .. } => {
+        let _ = reply.send(Err(ClipboardError::Unsupported(Backend::Windows)));
+      }
+      Request::Fetch {
+        change,
+        format,
+        reply,
+      } => {
+        let _ = reply.send(self.fetch(&change, &format));
+      }
+    }
+  }
+
+  /// Reads the content of a lazy change from the clipboard.
+  fn fetch(&self, change: &ChangeId, format: &AvailableFormat) -> Result<Body, ClipboardError> {
+    let _clipboard =
+      Clipboard::new_attempts(10).map_err(|e| ClipboardError::ReadError(e.to_string()))?;
+
+    if clipboard_win::seq_num() != self.change_seq {
+      return Err(ClipboardError::ContentChanged);
+    }
+
+    self.extractor.fetch(
+      change,
+      format,
+      &mut WinFormats {
+        html_format: self.html_format,
+        png_format: self.png_format,
+        rtf_format: self.rtf_format,
+        url_format: self.url_format,
+        exclude_format: self.exclude_format,
+        custom_formats: &self.custom_formats,
+      },
+    )
+  }
+}
+
+/// Reads each kind of content from the clipboard, which must be open.
+struct WinFormats<'a> {
+  html_format: Option<clipboard_win::formats::Html>,
+  png_format: Option<NonZeroU32>,
+  rtf_format: Option<NonZeroU32>,
+  url_format: Option<NonZeroU32>,
+  exclude_format: Option<NonZeroU32>,
+  custom_formats: &'a HashMap<Arc<str>, NonZeroU32>,
+}
 
-    use crate::image::convert_dib_to_png;
+impl FormatReader for WinFormats<'_> {
+  fn custom_format(
+    &mut self,
+    name: &str,
+    max_size: Option<usize>,
+  ) -> Result<Option<Vec<u8>>, ExtractionError> {
+    match self.custom_formats.get(name) {
+      Some(id) => WinObserver::extract_clipboard_format(id.get(), max_size),
+      None => Ok(None),
+    }
+  }
 
-    let max_image_bytes = self.max_image_size;
+  fn image(
+    &mut self,
+    max_image_bytes: Option<usize>,
+  ) -> Result<Option<SourceImage>, ExtractionError> {
+    use clipboard_win::formats;
 
     if let Some(png_code) = self.png_format
-      && let Some(png_bytes) = Self::extract_clipboard_format(png_code.get(), max_image_bytes)?
+      && let Some(png_bytes) =
+        WinObserver::extract_clipboard_format(png_code.get(), max_image_bytes)?
     {
       debug!("Loaded png from clipboard");
-      Ok(Some(png_bytes))
-    } else if let Some(bytes) = Self::extract_clipboard_format(formats::CF_DIBV5, max_image_bytes)?
-      && let Ok(png_bytes) = convert_dib_to_png(&bytes).ok_or(ExtractionError::ConversionError)
+      Ok(Some(SourceImage::Encoded {
+        bytes: png_bytes,
+        mime_type: MimeType::Png,
+      }))
+    } else if let Some(bytes) =
+      WinObserver::extract_clipboard_format(formats::CF_DIBV5, max_image_bytes)?
     {
-      debug!("Loaded DIBV5 from clipboard. Converting to PNG...");
-
-      Ok(Some(png_bytes))
-    } else if let Some(bytes) = Self::extract_clipboard_format(formats::CF_DIB, max_image_bytes)?
-      && let Ok(png_bytes) = convert_dib_to_png(&bytes).ok_or(ExtractionError::ConversionError)
+      debug!("Loaded DIBV5 from clipboard");
+      Ok(Some(SourceImage::Dib(bytes)))
+    } else if let Some(bytes) =
+      WinObserver::extract_clipboard_format(formats::CF_DIB, max_image_bytes)?
     {
-      debug!("Loaded DIB from clipboard. Converting to PNG...");
-
-      Ok(Some(png_bytes))
+      debug!("Loaded DIB from clipboard");
+      Ok(Some(SourceImage::Dib(bytes)))
     } else {
       Ok(None)
     }
   }
 
-  pub(super) fn extract_files_list(&self) -> Result<Option<Vec<PathBuf>>, ExtractionError> {
-    match format_is_valid(formats::FileList.into(), self.max_size)? {
+  fn files_list(
+    &mut self,
+    max_size: Option<usize>,
+  ) -> Result<Option<Vec<PathBuf>>, ExtractionError> {
+    match format_is_valid(formats::FileList.into(), max_size)? {
       true => {
         let mut files_list: Vec<PathBuf> = Vec::new();
         if let Ok(_num_files) = formats::FileList.read_clipboard(&mut files_list) {
@@ -160,123 +262,144 @@ impl WinObserver {
     }
   }
 
-  fn extract_clipboard_content(&self) -> Result<Option<Body>, ExtractionError> {
-    let max_bytes = self.max_size;
-
-    for (name, id) in self.custom_formats.iter() {
-      if let Some(bytes) = Self::extract_clipboard_format(id.get(), max_bytes)? {
-        debug!("Found content with custom format `{name}`");
+  fn html(&mut self) -> Result<Option<HtmlContent>, ExtractionError> {
+    let Some(html_format) = self.html_format else {
+      return Ok(None);
+    };
 
-        return Ok(Some(Body::Custom {
-          name: name.clone(),
-          data: bytes,
-        }));
-      }
+    match WinObserver::extract_clipboard_format(html_format.code(), None)? {
+      Some(bytes) => match parse_cf_html(&bytes) {
+        Some(html) => Ok(Some(html)),
+        None => {
+          debug!("Found HTML with an invalid header");
+          Ok(None)
+        }
+      },
+      None => Ok(None),
     }
+  }
+
+  fn uri_list(&mut self, max_size: Option<usize>) -> Result<Option<Vec<Url>>, ExtractionError> {
+    let Some(url_code) = self.url_format else {
+      return Ok(None);
+    };
 
-    if let Some(image_bytes) = self.extract_image_bytes()? {
-      let image_path = if let Some(mut files_list) = self.extract_files_list()?
-        && files_list.len() == 1
-      {
-        Some(files_list.remove(0))
-      } else {
-        None
-      };
-
-      Ok(Some(Body::Image(ClipboardImage {
-        bytes: image_bytes,
-        path: image_path,
-      })))
-    } else if let Some(mut files_list) = self.extract_files_list()? {
-      // If there is just one file in the list and it's an image,
-      // we save it directly as an image
-      use crate::image::{convert_file_to_png, file_is_image};
-
-      // We check if there is just one file
-      if files_list.len() == 1
-        && let Some(path) = files_list.first()
-
-        // Then, if it's an image
-        && file_is_image(path)
-        // Then, if the size is within the allowed range
-        && self.max_image_size.is_none_or(|max| path.metadata().is_ok_and(|metadata| max as u64 > metadata.len()))
-        // Then, if the bytes are readable and the conversion to png is successful
-        && let Some(png_bytes) = convert_file_to_png(path)
-      //
-      // Only if all of these are true, we save it as an image
-      {
-        debug!("Found file path with image format. Processing it as an image...");
-
-        let image_path = files_list.remove(0);
-
-        Ok(Some(Body::Image(ClipboardImage {
-          bytes: png_bytes,
-          path: Some(image_path),
-        })))
-      } else {
-        Ok(Some(Body::FileList(files_list)))
+    // Browsers put a single link in this format, as a null-terminated UTF-16 string
+    match WinObserver::extract_clipboard_format(url_code.get(), max_size)? {
+      Some(bytes) => {
+        let wide: Vec<u16> = bytes
+          .chunks_exact(2)
+          .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+          .take_while(|c| *c != 0)
+          .collect();
+
+        match Url::parse(String::from_utf16_lossy(&wide).trim()) {
+          Ok(url) if url.scheme() != "file" => {
+            debug!("Found URI list");
+            Ok(Some(vec![url]))
+          }
+          _ => Ok(None),
+        }
       }
-    } else {
-      let mut text = String::new();
+      None => Ok(None),
+    }
+  }
 
-      if let Some(html_parser) = self.html_format
-        && let Ok(_) = html_parser.read_clipboard(&mut text)
-      {
-        debug!("Extracted HTML content from clipboard");
+  fn rtf(&mut self) -> Result<Option<String>, ExtractionError> {
+    let Some(rtf_code) = self.rtf_format else {
+      return Ok(None);
+    };
 
-        Ok(Some(Body::Html(text)))
-      } else if let Ok(_num_bytes) = formats::Unicode.read_clipboard(&mut text) {
-        debug!("Extracted plain text from clipboard");
+    match WinObserver::extract_clipboard_format(rtf_code.get(), None)? {
+      Some(bytes) => {
+        // The data may be padded with null bytes
+        let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
 
-        Ok(Some(Body::PlainText(text)))
-      } else {
-        Ok(None)
+        Ok(Some(String::from_utf8_lossy(&bytes[..end]).into_owned()))
       }
+      None => Ok(None),
     }
   }
 
-  pub(super) fn get_clipboard_content(&self) -> Result<Option<Body>, ClipboardError> {
-    let _clipboard =
-      Clipboard::new_attempts(10).map_err(|e| ClipboardError::ReadError(e.to_string()))?;
+  fn plain_text(&mut self) -> Result<Option<String>, ExtractionError> {
+    let mut text = String::new();
+
+    if let Ok(_num_bytes) = formats::Unicode.read_clipboard(&mut text) {
+      Ok(Some(text))
+    } else {
+      Ok(None)
+    }
+  }
+
+  fn probe(&mut self, kind: FormatKind, name: Option<&str>) -> Result<Probe, ExtractionError> {
+    let available = |id: u32| clipboard_win::is_format_avail(id);
+    // Only the formats that are read as they are have a meaningful size
+    let exact_size = |id: u32| Probe::Available {
+      size: clipboard_win::size(id).map(NonZeroUsize::get),
+    };
 
-    match self.extract_clipboard_content() {
-      // Found content
-      Ok(Some(content)) => Ok(Some(content)),
-      // Non-fatal errors, we just return None
-      Err(ExtractionError::EmptyContent) => {
-        debug!("Found empty content, skipping it...");
-        Ok(None)
+    let probe = match kind {
+      FormatKind::Custom => match name.and_then(|name| self.custom_formats.get(name)) {
+        Some(id) if available(id.get()) => exact_size(id.get()),
+        _ => Probe::Missing,
+      },
+      FormatKind::Image => match self.png_format {
+        Some(png_code) if available(png_code.get()) => exact_size(png_code.get()),
+        _ if available(formats::CF_DIBV5) || available(formats::CF_DIB) => {
+          Probe::Available { size: None }
+        }
+        _ => Probe::Missing,
+      },
+      FormatKind::FileList if available(formats::CF_HDROP) => Probe::Available { size: None },
+      FormatKind::Html if self.html_format.is_some_and(|html| available(html.code())) => {
+        Probe::Available { size: None }
+      }
+      FormatKind::UriList if self.url_format.is_some_and(|url| available(url.get())) => {
+        Probe::Available { size: None }
+      }
+      FormatKind::Rtf if self.rtf_format.is_some_and(|rtf| available(rtf.get())) => {
+        Probe::Available { size: None }
       }
-      Err(ExtractionError::SizeTooLarge) => {
-        debug!("Found content beyond allowed size, skipping it...");
-        Ok(None)
+      FormatKind::PlainText if available(formats::CF_UNICODETEXT) => {
+        Probe::Available { size: None }
       }
+      _ => Probe::Missing,
+    };
 
-      // Actual error, we send it
-      Err(ExtractionError::ConversionError) => Err(ClipboardError::ImageConversion),
-      // There was content but we could not read it
-      Ok(None) => Err(ClipboardError::NoMatchingFormat),
-    }
+    Ok(probe)
+  }
+
+  fn is_concealed(&mut self) -> bool {
+    self
+      .exclude_format
+      .is_some_and(|id| clipboard_win::is_format_avail(id.get()))
   }
 }
 
 impl Observer for WinObserver {
-  fn observe(&mut self, body_senders: Arc<BodySenders>) {
+  fn observe(&mut self, sink: ClipboardSink) {
     info!("Started monitoring the clipboard");
 
     while !self.stop.load(Ordering::Relaxed) {
+      while let Some(request) = sink.next_request() {
+        self.handle_request(request);
+      }
+
       let monitor = &mut self.monitor;
 
       match monitor.try_recv() {
         Ok(true) => {
           match self.get_clipboard_content() {
-            Ok(Some(body)) => {
-              body_senders.send_all(Ok(Arc::new(body)));
+            Ok(Some(content)) => {
+              sink.send_event(
+                ClipboardEvent::new(ClipboardKind::Clipboard, content)
+                  .with_change_counter(self.change_seq.map(|seq| u64::from(seq.get()))),
+              );
             }
             Err(e) => {
               error!("{e}");
 
-              body_senders.send_all(Err(e));
+              sink.send_error(e);
             }
             // Found content but ignored it (empty or too large)
             Ok(None) => {}
@@ -291,7 +414,7 @@ impl Observer for WinObserver {
 
           error!("{error}");
 
-          body_senders.send_all(Err(error));
+          sink.send_error(error);
 
           error!("Fatal error, terminating clipboard watcher");
           break;
@@ -0,0 +1,20 @@
+use crate::{
+  Backend, driver::Driver, error::ClipboardError, extraction::Extractor, sink::ClipboardSink,
+  x11::observer::X11Observer,
+};
+
+impl Driver {
+  /// Construct [`Driver`] and spawn a thread for monitoring the X11 clipboard with the given backend
+  pub(crate) fn new_x11(
+    backend: Backend,
+    sink: ClipboardSink,
+    extractor: Extractor,
+  ) -> Result<Self, ClipboardError> {
+    let interval = sink.interval();
+    let kinds = sink.kinds().to_vec();
+
+    Self::spawn(backend, sink, move |stop| {
+      X11Observer::new(stop, backend, interval, extractor, kinds)
+    })
+  }
+}
@@ -0,0 +1,963 @@
+use std::{
+  collections::{HashMap, VecDeque},
+  sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+  },
+  time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use log::{debug, error, info};
+use x11rb::{
+  COPY_DEPTH_FROM_PARENT, CURRENT_TIME, NONE,
+  connection::{Connection, RequestConnection},
+  errors::{ConnectionError, ReplyError},
+  protocol::{
+    Event,
+    xfixes::{self, ConnectionExt as _, SelectionEventMask},
+    xproto::{
+      Atom, AtomEnum, ConnectionExt as _, CreateWindowAux, EventMask, GetPropertyReply, PropMode,
+      Property, SELECTION_NOTIFY_EVENT, SelectionNotifyEvent, SelectionRequestEvent, Timestamp,
+      Window, WindowClass,
+    },
+  },
+  rust_connection::RustConnection,
+  wrapper::ConnectionExt as _,
+};
+
+use crate::{
+  Backend, ClipboardEvent, ClipboardKind, SourceApp,
+  error::{ClipboardError, ExtractionError},
+  extraction::Extractor,
+  observer::Observer,
+  sink::{ClipboardSink, Request},
+  targets::{TargetFormats, TargetReader, body_targets},
+};
+
+x11rb::atom_manager! {
+  Atoms: AtomsCookie {
+    CLIPBOARD,
+    PRIMARY,
+    TARGETS,
+    TIMESTAMP,
+    INCR,
+    TRANSFER: b"CLIPBOARD_WATCHER_TRANSFER",
+    _NET_WM_PID,
+    WM_CLIENT_LEADER,
+  }
+}
+
+/// How long to wait for the owner of the clipboard to answer a conversion request.
+const TRANSFER_TIMEOUT: Duration = Duration::from_secs(1);
+
+impl From<ConnectionError> for ExtractionError {
+  fn from(e: ConnectionError) -> Self {
+    Self::ReadError(e.to_string())
+  }
+}
+
+impl From<ReplyError> for ExtractionError {
+  fn from(e: ReplyError) -> Self {
+    Self::ReadError(e.to_string())
+  }
+}
+
+fn init_error(e: impl std::fmt::Display) -> ClipboardError {
+  ClipboardError::InitializationError(e.to_string())
+}
+
+fn monitor_error(e: impl std::fmt::Display) -> ClipboardError {
+  ClipboardError::MonitorFailed(e.to_string())
+}
+
+fn write_error(e: impl std::fmt::Display) -> ClipboardError {
+  ClipboardError::WriteError(e.to_string())
+}
+
+/// A selection owned by the observer, after a write.
+struct OwnedSelection {
+  time: Timestamp,
+  targets: Vec<(Atom, Bytes)>,
+}
+
+/// The owner of a selection, as seen when polling.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct SelectionOwner {
+  window: Window,
+  // The time at which the selection was acquired, if the owner supports the `TIMESTAMP` target
+  timestamp: Option<Timestamp>,
+}
+
+/// How the changes of the selections are detected.
+enum Watcher {
+  /// The XFixes extension sends an event for every change.
+  XFixes,
+  /// The owners of the selections are checked at every interval.
+  Polling(Vec<(ClipboardKind, Option<SelectionOwner>)>),
+}
+
+/// A change of one of the monitored selections.
+struct SelectionChange {
+  kind: ClipboardKind,
+  selection: Atom,
+  time: Timestamp,
+  // The window that owns the selection, which identifies the source application
+  owner: Window,
+}
+
+/// The connection to the X server, with the invisible window used to receive the selection events and the transferred data.
+struct X11Connection {
+  conn: RustConnection,
+  window: Window,
+  atoms: Atoms,
+  atom_names: HashMap<Atom, String>,
+  // Events received while waiting for a transfer, to be processed by the main loop
+  pending_events: VecDeque<Event>,
+  owned: HashMap<Atom, OwnedSelection>,
+}
+
+pub(super) struct X11Observer {
+  stop: Arc<AtomicBool>,
+  connection: X11Connection,
+  watcher: Watcher,
+  interval: Duration,
+  extractor: Extractor,
+  // The time of the latest change of each selection, to read its lazy content later on
+  change_times: HashMap<ClipboardKind, Timestamp>,
+}
+
+impl X11Observer {
+  pub(super) fn new(
+    stop: Arc<AtomicBool>,
+    backend: Backend,
+    interval: Duration,
+    extractor: Extractor,
+    kinds: Vec<ClipboardKind>,
+  ) -> Result<Self, ClipboardError> {
+    let polling = backend == Backend::X11Polling;
+
+    let (conn, screen_num) = x11rb::connect(None).map_err(init_error)?;
+
+    if !polling {
+      if conn
+        .extension_information(xfixes::X11_EXTENSION_NAME)
+        .map_err(init_error)?
+        .is_none()
+      {
+        return Err(ClipboardError::InitializationError(
+          "the XFixes extension is not available".to_string(),
+        ));
+      }
+
+      // The version must be negotiated before any other XFixes request is made
+      conn
+        .xfixes_query_version(5, 0)
+        .map_err(init_error)?
+        .reply()
+        .map_err(init_error)?;
+    }
+
+    let atoms = Atoms::new(&conn)
+      .map_err(init_error)?
+      .reply()
+      .map_err(init_error)?;
+
+    let screen = &conn.setup().roots[screen_num];
+    let window = conn.generate_id().map_err(init_error)?;
+    conn
+      .create_window(
+        COPY_DEPTH_FROM_PARENT,
+        window,
+        screen.root,
+        0,
+        0,
+        1,
+        1,
+        0,
+        WindowClass::INPUT_OUTPUT,
+        screen.root_visual,
+        &CreateWindowAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+      )
+      .map_err(init_error)?;
+
+    if !polling {
+      for &kind in &kinds {
+        conn
+          .xfixes_select_selection_input(
+            window,
+            atoms.selection(kind),
+            SelectionEventMask::SET_SELECTION_OWNER
+              | SelectionEventMask::SELECTION_WINDOW_DESTROY
+              | SelectionEventMask::SELECTION_CLIENT_CLOSE,
+          )
+          .map_err(init_error)?;
+      }
+    }
+
+    conn.flush().map_err(init_error)?;
+
+    let mut connection = X11Connection {
+      conn,
+      window,
+      atoms,
+      atom_names: HashMap::new(),
+      pending_events: VecDeque::new(),
+      owned: HashMap::new(),
+    };
+
+    let watcher = if polling {
+      // The current owners are not changes, so we only store them
+      let owners = kinds
+        .into_iter()
+        .map(|kind| Ok((kind, connection.selection_owner(atoms.selection(kind))?)))
+        .collect::<Result<_, ClipboardError>>()?;
+
+      Watcher::Polling(owners)
+    } else {
+      Watcher::XFixes
+    };
+
+    Ok(X11Observer {
+      stop,
+      connection,
+      watcher,
+      interval,
+      extractor,
+      change_times: HashMap::new(),
+    })
+  }
+}
+
+impl Atoms {
+  fn selection(&self, kind: ClipboardKind) -> Atom {
+    match kind {
+      ClipboardKind::Clipboard => self.CLIPBOARD,
+      ClipboardKind::Primary => self.PRIMARY,
+    }
+  }
+}
+
+impl X11Observer {
+  /// Returns the next change of the monitored selections, if there is one.
+  fn next_change(&mut self) -> Result<Option<SelectionChange>, ClipboardError> {
+    while let Some(event) = self.connection.next_event().map_err(monitor_error)? {
+      match event {
+        // Another client wants the content of a selection that we own
+        Event::SelectionRequest(request) => self
+          .connection
+          .answer_request(request)
+          .map_err(monitor_error)?,
+        Event::SelectionClear(clear) => {
+          self.connection.owned.remove(&clear.selection);
+        }
+        Event::XfixesSelectionNotify(event) if matches!(self.watcher, Watcher::XFixes) => {
+          let kind = if event.selection == self.connection.atoms.PRIMARY {
+            ClipboardKind::Primary
+          } else {
+            ClipboardKind::Clipboard
+          };
+
+          if event.owner == NONE {
+            debug!("The {kind:?} selection was cleared");
+            continue;
+          }
+
+          // Our own writes are reported as soon as they are made
+          if event.owner == self.connection.window {
+            continue;
+          }
+
+          return Ok(Some(SelectionChange {
+            kind,
+            selection: event.selection,
+            time: event.timestamp,
+            owner: event.owner,
+          }));
+        }
+        // Other events are not relevant for monitoring
+        _ => {}
+      }
+    }
+
+    match &mut self.watcher {
+      Watcher::XFixes => Ok(None),
+      Watcher::Polling(owners) => {
+        for (kind, owner) in owners.iter_mut() {
+          let selection = self.connection.atoms.selection(*kind);
+          let current = self.connection.selection_owner(selection)?;
+
+          if current == *owner {
+            continue;
+          }
+
+          *owner = current;
+
+          let Some(current) = current else {
+            debug!("The {kind:?} selection was cleared");
+            continue;
+          };
+
+          // Our own writes are reported as soon as they are made
+          if current.window == self.connection.window {
+            continue;
+          }
+
+          return Ok(Some(SelectionChange {
+            kind: *kind,
+            selection,
+            time: CURRENT_TIME,
+            owner: current.window,
+          }));
+        }
+
+        Ok(None)
+      }
+    }
+  }
+
+  fn handle_request(&mut self, request: Request, sink: &ClipboardSink) {
+    match request {
+      Request::Set { kind, body, reply } => {
+        let selection = self.connection.atoms.selection(kind);
+        let result = self
+          .connection
+          .own_selection(selection, body_targets(&body));
+
+        if result.is_ok() {
+          self.extractor.invalidate(kind);
+          sink.send_own_write(kind, body);
+        }
+
+        let _ = reply.send(result);
+      }
+      Request::Fetch {
+        change,
+        format,
+        reply,
+      } => {
+        let result = match self.selection_changed(change.kind) {
+          Ok(true) => Err(ClipboardError::ContentChanged),
+          Ok(false) => {
+            let text_decoding = self.extractor.text_decoding();
+            // Reading at the time of the change also makes the owners that check it refuse the request
+            let mut reader = SelectionReader {
+              selection: self.connection.atoms.selection(change.kind),
+              time: self
+                .change_times
+                .get(&change.kind)
+                .copied()
+                .unwrap_or(CURRENT_TIME),
+              targets: HashMap::new(),
+              connection: &mut self.connection,
+            };
+
+            self.extractor.fetch(
+              &change,
+              &format,
+              &mut TargetFormats::new(&mut reader, text_decoding),
+            )
+          }
+          Err(e) => Err(e),
+        };
+
+        let _ = reply.send(result);
+      }
+    }
+  }
+
+  /// Checks whether a selection changed since its latest change was detected, including the changes that were not processed yet.
+  ///
+  /// Many owners ignore the time of the requests, so they would hand over their new content for an older change.
+  fn selection_changed(&mut self, kind: ClipboardKind) -> Result<bool, ClipboardError> {
+    let selection = self.connection.atoms.selection(kind);
+
+    match &self.watcher {
+      Watcher::XFixes => {
+        // Every new owner is notified, so the change is among the events that were not processed yet
+        self.connection.receive_events().map_err(monitor_error)?;
+
+        Ok(self.connection.pending_events.iter().any(
+          |event| matches!(event, Event::XfixesSelectionNotify(n) if n.selection == selection),
+        ))
+      }
+      Watcher::Polling(owners) => {
+        let previous = owners
+          .iter()
+          .find(|(k, _)| *k == kind)
+          .and_then(|(_, owner)| *owner);
+
+        Ok(self.connection.selection_owner(selection)? != previous)
+      }
+    }
+  }
+}
+
+impl Observer for X11Observer {
+  fn observe(&mut self, sink: ClipboardSink) {
+    info!("Started monitoring the clipboard");
+
+    while !self.stop.load(Ordering::Relaxed) {
+      while let Some(request) = sink.next_request() {
+        self.handle_request(request, &sink);
+      }
+
+      match self.next_change() {
+        Ok(Some(change)) => {
+          self.change_times.insert(change.kind, change.time);
+
+          let source_app = self.connection.source_app(change.owner);
+
+          if let Some(app) = &source_app
+            && sink.is_ignored(app)
+          {
+            debug!("Found a change from ignored application {app:?}, skipping it...");
+            // The previous content is gone, and the one of the ignored application must not be fetched
+            self.extractor.invalidate(change.kind);
+            continue;
+          }
+
+          let text_decoding = self.extractor.text_decoding();
+          let mut reader = SelectionReader {
+            selection: change.selection,
+            time: change.time,
+            targets: HashMap::new(),
+            connection: &mut self.connection,
+          };
+
+          match self.extractor.get_clipboard_content(
+            change.kind,
+            &mut TargetFormats::new(&mut reader, text_decoding),
+          ) {
+            Ok(Some(content)) => sink.send_event(
+              ClipboardEvent::new(change.kind, content)
+                .with_change_counter(
+                  (change.time != CURRENT_TIME).then_some(u64::from(change.time)),
+                )
+                .with_source_app(source_app),
+            ),
+            Err(e) => {
+              error!("{e}");
+              sink.send_error(e);
+            }
+            // Found content but ignored it (empty or beyond allowed size)
+            Ok(None) => {}
+          }
+        }
+        Ok(None) => {
+          // No change, waiting
+          std::thread::sleep(self.interval);
+        }
+        Err(error) => {
+          error!("{error}");
+
+          sink.send_error(error);
+
+          error!("Fatal error, terminating clipboard watcher");
+          break;
+        }
+      }
+    }
+  }
+}
+
+impl X11Connection {
+  fn next_event(&mut self) -> Result<Option<Event>, ConnectionError> {
+    match self.pending_events.pop_front() {
+      Some(event) => Ok(Some(event)),
+      None => self.conn.poll_for_event(),
+    }
+  }
+
+  /// Stores the events received so far, so that they can be inspected before being processed.
+  fn receive_events(&mut self) -> Result<(), ConnectionError> {
+    while let Some(event) = self.conn.poll_for_event()? {
+      self.pending_events.push_back(event);
+    }
+
+    Ok(())
+  }
+
+  /// Waits for an event matching the predicate, storing the unrelated ones so that they can be processed later on.
+  ///
+  /// Returns `None` if the clipboard owner does not answer in time.
+  fn wait_for_event(
+    &mut self,
+    predicate: impl Fn(&Event) -> bool,
+  ) -> Result<Option<Event>, ConnectionError> {
+    let deadline = Instant::now() + TRANSFER_TIMEOUT;
+
+    while Instant::now() < deadline {
+      match self.conn.poll_for_event()? {
+        Some(event) if predicate(&event) => return Ok(Some(event)),
+        Some(event) => self.pending_events.push_back(event),
+        None => std::thread::sleep(Duration::from_millis(1)),
+      }
+    }
+
+    debug!("Timed out while waiting for the clipboard owner");
+    Ok(None)
+  }
+
+  /// Asks the owner of the selection to convert it to the given target and reads the result.
+  fn convert_selection(
+    &mut self,
+    selection: Atom,
+    target: Atom,
+    max_size: Option<usize>,
+    time: Timestamp,
+  ) -> Result<Option<Vec<u8>>, ExtractionError> {
+    let window = self.window;
+    let property = self.atoms.TRANSFER;
+
+    self
+      .conn
+      .convert_selection(window, selection, target, property, time)?;
+    self.conn.flush()?;
+
+    let notify = match self.wait_for_event(|event| {
+      matches!(event, Event::SelectionNotify(n) if n.requestor == window && n.target == target)
+    })? {
+      Some(Event::SelectionNotify(notify)) => notify,
+      _ => return Ok(None),
+    };
+
+    // The owner refused the conversion
+    if notify.property == NONE {
+      return Ok(None);
+    }
+
+    // We only query the type and size at first, so that we can skip large items without reading them
+    let header = self
+      .conn
+      .get_property(false, window, property, AtomEnum::ANY, 0, 0)?
+      .reply()?;
+
+    if header.type_ == self.atoms.INCR {
+      return self.read_incremental(max_size);
+    }
+
+    let size = header.bytes_after as usize;
+
+    if size == 0 || max_size.is_some_and(|max| size > max) {
+      self.conn.delete_property(window, property)?;
+      self.conn.flush()?;
+
+      return if size == 0 {
+        // Found content but it was empty, trigger early exit
+        Err(ExtractionError::EmptyContent)
+      } else {
+        Err(ExtractionError::SizeTooLarge(Some(size)))
+      };
+    }
+
+    let reply = self
+      .conn
+      .get_property(
+        true,
+        window,
+        property,
+        AtomEnum::ANY,
+        0,
+        size.div_ceil(4) as u32,
+      )?
+      .reply()?;
+    self.conn.flush()?;
+
+    Ok(Some(reply.value))
+  }
+
+  /// Reads data sent with the INCR protocol, used by the owners for large transfers.
+  fn read_incremental(
+    &mut self,
+    max_size: Option<usize>,
+  ) -> Result<Option<Vec<u8>>, ExtractionError> {
+    let window = self.window;
+    let property = self.atoms.TRANSFER;
+
+    // The INCR property holds a lower bound for the size of the data
+    let lower_bound = self
+      .conn
+      .get_property(false, window, property, AtomEnum::ANY, 0, 1)?
+      .reply()?
+      .value32()
+      .and_then(|mut values| values.next())
+      .unwrap_or(0) as usize;
+
+    // Deleting the property tells the owner to start sending the chunks
+    self.conn.delete_property(window, property)?;
+    self.conn.flush()?;
+
+    if max_size.is_some_and(|max| lower_bound > max) {
+      return Err(ExtractionError::SizeTooLarge(Some(lower_bound)));
+    }
+
+    let mut data = Vec::with_capacity(lower_bound);
+
+    loop {
+      if self
+        .wait_for_event(|event| {
+          matches!(event, Event::PropertyNotify(n) if n.window == window && n.atom == property && n.state == Property::NEW_VALUE)
+        })?
+        .is_none()
+      {
+        return Ok(None);
+      }
+
+      let chunk = self
+        .conn
+        .get_property(true, window, property, AtomEnum::ANY, 0, u32::MAX)?
+        .reply()?;
+      self.conn.flush()?;
+
+      // A zero-length chunk marks the end of the transfer
+      if chunk.value.is_empty() {
+        break;
+      }
+
+      data.extend_from_slice(&chunk.value);
+
+      if max_size.is_some_and(|max| data.len() > max) {
+        return Err(ExtractionError::SizeTooLarge(Some(data.len())));
+      }
+    }
+
+    if data.is_empty() {
+      Err(ExtractionError::EmptyContent)
+    } else {
+      Ok(Some(data))
+    }
+  }
+
+  /// Returns the current owner of the selection, or `None` if the selection is not owned.
+  fn selection_owner(&mut self, selection: Atom) -> Result<Option<SelectionOwner>, ClipboardError> {
+    let window = self
+      .conn
+      .get_selection_owner(selection)
+      .map_err(monitor_error)?
+      .reply()
+      .map_err(monitor_error)?
+      .owner;
+
+    if window == NONE {
+      return Ok(None);
+    }
+
+    // We would be waiting for ourselves to answer
+    if window == self.window {
+      let timestamp = self.owned.get(&selection).map(|owned| owned.time);
+      return Ok(Some(SelectionOwner { window, timestamp }));
+    }
+
+    // An owner can set new content without giving up the selection, so we also check when it was acquired
+    let timestamp =
+      match self.convert_selection(selection, self.atoms.TIMESTAMP, None, CURRENT_TIME) {
+        Ok(Some(bytes)) if bytes.len() >= 4 => {
+          Some(u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        }
+        Err(ExtractionError::ReadError(e)) => return Err(ClipboardError::MonitorFailed(e)),
+        // The owner does not support the target, so we can only rely on the owner window
+        _ => None,
+      };
+
+    Ok(Some(SelectionOwner { window, timestamp }))
+  }
+
+  /// Reads a property of a window, returning `None` if it is not set or cannot be read.
+  fn window_property(
+    &self,
+    window: Window,
+    property: impl Into<Atom>,
+    type_: AtomEnum,
+  ) -> Option<GetPropertyReply> {
+    let reply = self
+      .conn
+      .get_property(false, window, property, type_, 0, 1024)
+      .ok()?
+      .reply()
+      .inspect_err(|e| debug!("Failed to read window property: {e}"))
+      .ok()?;
+
+    (reply.type_ != NONE).then_some(reply)
+  }
+
+  /// Finds the application that owns a window, from the properties set on it.
+  fn source_app(&self, window: Window) -> Option<SourceApp> {
+    // Toolkits usually own the selections with a hidden window, whose properties are set on the leader of its group
+    let leader = self
+      .window_property(window, self.atoms.WM_CLIENT_LEADER, AtomEnum::WINDOW)
+      .and_then(|reply| reply.value32()?.next())
+      .filter(|leader| *leader != NONE && *leader != window);
+
+    let mut app = SourceApp::default();
+
+    for window in std::iter::once(window).chain(leader) {
+      if app.pid.is_none() {
+        app.pid = self
+          .window_property(window, self.atoms._NET_WM_PID, AtomEnum::CARDINAL)
+          .and_then(|reply| reply.value32()?.next());
+      }
+
+      // The class holds the instance name and the class name, each terminated by a null byte
+      if app.class.is_none()
+        && let Some(reply) = self.window_property(window, AtomEnum::WM_CLASS, AtomEnum::STRING)
+      {
+        let mut parts = reply
+          .value
+          .split(|b| *b == 0)
+          .filter(|part| !part.is_empty())
+          .map(|part| String::from_utf8_lossy(part).into_owned());
+
+        app.name = parts.next();
+        app.class = parts.next();
+      }
+    }
+
+    if app.name.is_none()
+      && let Some(pid) = app.pid
+    {
+      app.name = std::fs::read_to_string(format!("/proc/{pid}/comm"))
+        .ok()
+        .map(|name| name.trim_end().to_string());
+    }
+
+    (app != SourceApp::default()).then_some(app)
+  }
+
+  /// Returns the current time of the server, which is needed to acquire a selection.
+  fn server_time(&mut self) -> Result<Timestamp, ClipboardError> {
+    let window = self.window;
+    let property = self.atoms.TRANSFER;
+
+    // Appending nothing to a property leaves it unchanged, but still generates an event with the current time
+    self
+      .conn
+      .change_property8(PropMode::APPEND, window, property, AtomEnum::STRING, &[])
+      .map_err(write_error)?;
+    self.conn.flush().map_err(write_error)?;
+
+    match self
+      .wait_for_event(|event| {
+        // Earlier transfers may have left notifications for the deletion of the property
+        matches!(event, Event::PropertyNotify(n) if n.window == window && n.atom == property && n.state == Property::NEW_VALUE)
+      })
+      .map_err(write_error)?
+    {
+      Some(Event::PropertyNotify(notify)) => Ok(notify.time),
+      _ => Err(ClipboardError::WriteError(
+        "timed out while waiting for the X server".to_string(),
+      )),
+    }
+  }
+
+  /// Returns the largest target that can be sent to another client in a single request.
+  ///
+  /// Larger ones would need the INCR protocol, which is not supported for writing.
+  fn max_property_size(&self) -> usize {
+    self.conn.maximum_request_bytes().saturating_sub(32)
+  }
+
+  /// Acquires the selection, offering the given targets to the other clients.
+  fn own_selection(
+    &mut self,
+    selection: Atom,
+    targets: Vec<(String, Bytes)>,
+  ) -> Result<(), ClipboardError> {
+    let max_size = self.max_property_size();
+
+    // The requests for larger targets would be refused, so pasting would fail without any error
+    if let Some((name, data)) = targets.iter().find(|(_, data)| data.len() > max_size) {
+      return Err(ClipboardError::WriteError(format!(
+        "the content is too large for the `{name}` target ({} bytes, at most {max_size})",
+        data.len()
+      )));
+    }
+
+    // Send all of the requests first, then collect the replies
+    let cookies = targets
+      .iter()
+      .map(|(name, _)| self.conn.intern_atom(false, name.as_bytes()))
+      .collect::<Result<Vec<_>, ConnectionError>>()
+      .map_err(write_error)?;
+
+    let mut owned_targets = Vec::with_capacity(targets.len());
+
+    for (cookie, (name, data)) in cookies.into_iter().zip(targets) {
+      let atom = cookie.reply().map_err(write_error)?.atom;
+      self.atom_names.insert(atom, name);
+      owned_targets.push((atom, data));
+    }
+
+    let time = self.server_time()?;
+
+    self
+      .conn
+      .set_selection_owner(self.window, selection, time)
+      .map_err(write_error)?;
+
+    let owner = self
+      .conn
+      .get_selection_owner(selection)
+      .map_err(write_error)?
+      .reply()
+      .map_err(write_error)?
+      .owner;
+
+    if owner != self.window {
+      return Err(ClipboardError::WriteError(
+        "could not acquire the selection".to_string(),
+      ));
+    }
+
+    self.owned.insert(
+      selection,
+      OwnedSelection {
+        time,
+        targets: owned_targets,
+      },
+    );
+
+    Ok(())
+  }
+
+  /// Sends the content of an owned selection to the client that requested it, or refuses the request if it cannot be satisfied.
+  fn answer_request(&mut self, request: SelectionRequestEvent) -> Result<(), ConnectionError> {
+    // Obsolete clients may not specify a property, in which case the target is used
+    let property = if request.property == NONE {
+      request.target
+    } else {
+      request.property
+    };
+
+    let max_size = self.max_property_size();
+
+    let granted = match self.owned.get(&request.selection) {
+      Some(owned) if request.time == CURRENT_TIME || request.time >= owned.time => {
+        if request.target == self.atoms.TARGETS {
+          let mut atoms = vec![self.atoms.TARGETS, self.atoms.TIMESTAMP];
+          atoms.extend(owned.targets.iter().map(|(atom, _)| *atom));
+
+          self.conn.change_property32(
+            PropMode::REPLACE,
+            request.requestor,
+            property,
+            AtomEnum::ATOM,
+            &atoms,
+          )?;
+          true
+        } else if request.target == self.atoms.TIMESTAMP {
+          self.conn.change_property32(
+            PropMode::REPLACE,
+            request.requestor,
+            property,
+            AtomEnum::INTEGER,
+            &[owned.time],
+          )?;
+          true
+        } else if let Some((_, data)) = owned
+          .targets
+          .iter()
+          .find(|(atom, _)| *atom == request.target)
+          && data.len() <= max_size
+        {
+          self.conn.change_property8(
+            PropMode::REPLACE,
+            request.requestor,
+            property,
+            request.target,
+            data,
+          )?;
+          true
+        } else {
+          false
+        }
+      }
+      _ => false,
+    };
+
+    let notify = SelectionNotifyEvent {
+      response_type: SELECTION_NOTIFY_EVENT,
+      sequence: 0,
+      time: request.time,
+      requestor: request.requestor,
+      selection: request.selection,
+      target: request.target,
+      property: if granted { property } else { NONE },
+    };
+
+    self
+      .conn
+      .send_event(false, request.requestor, EventMask::NO_EVENT, notify)?;
+    self.conn.flush()?;
+
+    Ok(())
+  }
+
+  /// Returns the names of the given atoms, asking the server only for the ones that are not cached yet.
+  fn atom_names(&mut self, atoms: &[Atom]) -> Result<Vec<String>, ExtractionError> {
+    // Send all of the requests first, then collect the replies
+    let cookies = atoms
+      .iter()
+      .filter(|atom| !self.atom_names.contains_key(atom))
+      .map(|&atom| Ok((atom, self.conn.get_atom_name(atom)?)))
+      .collect::<Result<Vec<_>, ConnectionError>>()?;
+
+    for (atom, cookie) in cookies {
+      let name = String::from_utf8_lossy(&cookie.reply()?.name).into_owned();
+      self.atom_names.insert(atom, name);
+    }
+
+    Ok(
+      atoms
+        .iter()
+        .filter_map(|atom| self.atom_names.get(atom).cloned())
+        .collect(),
+    )
+  }
+}
+
+/// Reads the content of a selection, as it was at the time of the change.
+struct SelectionReader<'a> {
+  connection: &'a mut X11Connection,
+  selection: Atom,
+  time: Timestamp,
+  targets: HashMap<String, Atom>,
+}
+
+impl TargetReader for SelectionReader<'_> {
+  fn targets(&mut self) -> Result<Vec<String>, ExtractionError> {
+    let targets_atom = self.connection.atoms.TARGETS;
+
+    let Some(bytes) =
+      self
+        .connection
+        .convert_selection(self.selection, targets_atom, None, self.time)?
+    else {
+      return Ok(Vec::new());
+    };
+
+    let atoms: Vec<Atom> = bytes
+      .chunks_exact(4)
+      .map(|chunk| u32::from_ne_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+      .collect();
+
+    let names = self.connection.atom_names(&atoms)?;
+
+    self.targets = names.iter().cloned().zip(atoms).collect();
+
+    Ok(names)
+  }
+
+  fn read_target(
+    &mut self,
+    target: &str,
+    max_size: Option<usize>,
+  ) -> Result<Option<Vec<u8>>, ExtractionError> {
+    match self.targets.get(target) {
+      Some(&atom) => self
+        .connection
+        .convert_selection(self.selection, atom, max_size, self.time),
+      None => Ok(None),
+    }
+  }
+}
@@ -0,0 +1,124 @@
+//! Tests of the options of the listener, applied to the items sent by a custom [`ClipboardBackend`].
+
+use std::{
+  sync::mpsc::{self, Receiver, Sender},
+  time::Duration,
+};
+
+use clipboard_watcher::{
+  Body, Bytes, ClipboardBackend, ClipboardContent, ClipboardEvent, ClipboardEventListener,
+  ClipboardEventListenerBuilder, ClipboardImage, ClipboardKind, ClipboardSink, ClipboardStream,
+  ColorType, FormatKind, MimeType, SkipReason,
+};
+use futures::StreamExt;
+
+/// Sends the bodies it receives to the sink, as they come.
+struct Relay(Receiver<(ClipboardKind, Body)>);
+
+impl ClipboardBackend for Relay {
+  fn observe(&mut self, sink: ClipboardSink) {
+    while !sink.is_stopped() {
+      if let Ok((kind, body)) = self.0.recv_timeout(sink.interval()) {
+        sink.send(kind, body);
+      }
+    }
+  }
+}
+
+struct Harness {
+  // Dropping the listener stops the backend
+  _listener: ClipboardEventListener,
+  stream: ClipboardStream,
+  tx: Sender<(ClipboardKind, Body)>,
+}
+
+impl Harness {
+  fn new(builder: ClipboardEventListenerBuilder) -> Self {
+    let (tx, rx) = mpsc::channel();
+
+    let mut listener = builder
+      .interval(Duration::from_millis(5))
+      .backend(Relay(rx))
+      .spawn()
+      .unwrap();
+    let stream = listener.new_stream(32);
+
+    Harness {
+      _listener: listener,
+      stream,
+      tx,
+    }
+  }
+
+  fn send(&self, body: Body) {
+    self.tx.send((ClipboardKind::Clipboard, body)).unwrap();
+  }
+
+  async fn next(&mut self) -> ClipboardEvent {
+    self.stream.next().await.unwrap().unwrap()
+  }
+}
+
+fn text(text: &str) -> Body {
+  Body::PlainText(text.to_string())
+}
+
+#[tokio::test]
+async fn sent_items_follow_the_options() {
+  let mut harness = Harness::new(
+    ClipboardEventListener::builder()
+      .with_custom_formats(["app/data"])
+      .max_size(4)
+      .disable_formats([FormatKind::Rtf])
+      .report_skipped(),
+  );
+
+  harness.send(Body::Rtf("{\\rtf1 hidden}".to_string()));
+  harness.send(text(""));
+  harness.send(Body::Custom {
+    name: "app/data".into(),
+    data: Bytes::from_static(b"too large"),
+  });
+  harness.send(text("shown"));
+
+  for reason in [SkipReason::Empty, SkipReason::TooLarge] {
+    match harness.next().await.content {
+      ClipboardContent::Skipped(skipped) => assert_eq!(skipped.reason, reason),
+      content => panic!("expected skipped content, got {content:?}"),
+    }
+  }
+
+  assert_eq!(harness.next().await.body(), Some(&text("shown")));
+}
+
+#[tokio::test]
+async fn sent_items_are_wrapped_like_the_system_clipboard() {
+  let mut harness = Harness::new(ClipboardEventListener::builder().snapshots());
+
+  harness.send(text("copied"));
+
+  match harness.next().await.content {
+    ClipboardContent::Snapshot(snapshot) => {
+      assert_eq!(snapshot.representations(), [text("copied")]);
+    }
+    content => panic!("expected a snapshot, got {content:?}"),
+  }
+}
+
+#[tokio::test]
+async fn sent_images_are_delivered_as_they_are() {
+  let mut harness = Harness::new(ClipboardEventListener::builder().image_thumbnails(16));
+
+  let image = Body::Image(ClipboardImage {
+    bytes: Bytes::from_static(b"not decoded"),
+    mime_type: MimeType::Png,
+    width: 1,
+    height: 1,
+    color_type: ColorType::Rgba8,
+    thumbnail: None,
+    path: None,
+  });
+  harness.send(image.clone());
+
+  assert_eq!(harness.next().await.body(), Some(&image));
+}
@@ -0,0 +1,195 @@
+//! Tests of the Wayland backend against a headless compositor.
+//!
+//! They need `sway` to be installed (weston does not support the data-control protocol), so they are ignored by default.
+//! Run them with `cargo test --test wayland -- --ignored`.
+
+#![cfg(all(unix, not(target_os = "macos")))]
+
+use std::{
+  env, fs,
+  os::unix::fs::DirBuilderExt,
+  path::PathBuf,
+  process::{Child, Command, Stdio},
+  sync::{
+    Mutex, PoisonError,
+    atomic::{AtomicU32, Ordering},
+  },
+  thread,
+  time::{Duration, Instant},
+};
+
+use clipboard_watcher::{
+  Backend, Body, ClipboardEvent, ClipboardEventListener, ClipboardEventListenerBuilder,
+  ClipboardStream,
+};
+use futures::StreamExt;
+
+// The compositor is found through the environment, which is shared by the tests that run at the same time
+static ENVIRONMENT: Mutex<()> = Mutex::new(());
+static NEXT_ID: AtomicU32 = AtomicU32::new(0);
+
+/// A headless sway compositor with its own runtime directory, which is stopped when dropped.
+struct Sway {
+  process: Child,
+  runtime_dir: PathBuf,
+  display: String,
+}
+
+impl Sway {
+  fn start() -> Self {
+    let runtime_dir = env::temp_dir().join(format!(
+      "clipboard-watcher-{}-{}",
+      std::process::id(),
+      NEXT_ID.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    // A runtime directory must only be accessible by its owner
+    fs::DirBuilder::new()
+      .mode(0o700)
+      .create(&runtime_dir)
+      .unwrap();
+
+    let config = runtime_dir.join("config");
+    fs::write(&config, "").unwrap();
+
+    let process = Command::new("sway")
+      .arg("--config")
+      .arg(&config)
+      .env("XDG_RUNTIME_DIR", &runtime_dir)
+      .env("WLR_BACKENDS", "headless")
+      .env("WLR_LIBINPUT_NO_DEVICES", "1")
+      .env("WLR_RENDERER", "pixman")
+      .env_remove("WAYLAND_DISPLAY")
+      .env_remove("DISPLAY")
+      .stdout(Stdio::null())
+      .stderr(Stdio::null())
+      .spawn()
+      .expect("sway must be installed");
+
+    let mut sway = Sway {
+      process,
+      runtime_dir,
+      display: String::new(),
+    };
+
+    let start = Instant::now();
+
+    sway.display = loop {
+      let socket = fs::read_dir(&sway.runtime_dir)
+        .unwrap()
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .find(|name| name.starts_with("wayland-") && !name.ends_with(".lock"));
+
+      if let Some(socket) = socket {
+        break socket;
+      }
+
+      assert!(
+        start.elapsed() < Duration::from_secs(10),
+        "sway did not start"
+      );
+      thread::sleep(Duration::from_millis(10));
+    };
+
+    sway
+  }
+
+  /// Spawns a listener connected to this compositor.
+  fn spawn(&self, builder: ClipboardEventListenerBuilder) -> ClipboardEventListener {
+    let _guard = ENVIRONMENT.lock().unwrap_or_else(PoisonError::into_inner);
+
+    // SAFETY: the environment is only changed while holding the lock
+    unsafe {
+      env::set_var("XDG_RUNTIME_DIR", &self.runtime_dir);
+      env::set_var("WAYLAND_DISPLAY", &self.display);
+    }
+
+    builder.spawn().unwrap()
+  }
+}
+
+impl Drop for Sway {
+  fn drop(&mut self) {
+    let _ = self.process.kill();
+    let _ = self.process.wait();
+    let _ = fs::remove_dir_all(&self.runtime_dir);
+  }
+}
+
+fn text(text: &str) -> Body {
+  Body::PlainText(text.to_string())
+}
+
+async fn next(stream: &mut ClipboardStream) -> ClipboardEvent {
+  tokio::time::timeout(Duration::from_secs(10), stream.next())
+    .await
+    .expect("no change was reported")
+    .unwrap()
+    .unwrap()
+}
+
+#[tokio::test]
+#[ignore = "needs sway"]
+async fn changes_are_reported() {
+  let sway = Sway::start();
+
+  let mut listener = sway.spawn(ClipboardEventListener::builder());
+  assert_eq!(listener.backend(), Backend::WaylandDataControl);
+  let mut stream = listener.new_stream(32);
+
+  let writer = sway.spawn(ClipboardEventListener::builder());
+  writer.set(text("copied")).await.unwrap();
+
+  let event = next(&mut stream).await;
+  assert_eq!(event.body(), Some(&text("copied")));
+  assert!(!event.own_write);
+}
+
+#[tokio::test]
+#[ignore = "needs sway"]
+async fn large_content_is_read_through_the_pipe() {
+  let sway = Sway::start();
+
+  let mut listener = sway.spawn(ClipboardEventListener::builder());
+  let mut stream = listener.new_stream(32);
+
+  // Much larger than the buffer of a pipe, so it takes several reads
+  let large = "0123456789abcdef".repeat(256 * 1024);
+  let writer = sway.spawn(ClipboardEventListener::builder());
+  writer.set(text(&large)).await.unwrap();
+
+  assert_eq!(next(&mut stream).await.body(), Some(&text(&large)));
+}
+
+#[tokio::test]
+#[ignore = "needs sway"]
+async fn own_writes_are_flagged() {
+  let sway = Sway::start();
+
+  let mut listener = sway.spawn(ClipboardEventListener::builder());
+  let mut stream = listener.new_stream(32);
+
+  listener.set(text("mine")).await.unwrap();
+
+  let event = next(&mut stream).await;
+  assert_eq!(event.body(), Some(&text("mine")));
+  assert!(event.own_write);
+}
+
+#[tokio::test]
+#[ignore = "needs sway"]
+async fn own_writes_can_be_ignored() {
+  let sway = Sway::start();
+
+  let mut listener = sway.spawn(ClipboardEventListener::builder().ignore_own_writes());
+  let mut stream = listener.new_stream(32);
+
+  listener.set(text("mine")).await.unwrap();
+
+  let writer = sway.spawn(ClipboardEventListener::builder());
+  writer.set(text("theirs")).await.unwrap();
+
+  let event = next(&mut stream).await;
+  assert_eq!(event.body(), Some(&text("theirs")));
+  assert!(!event.own_write);
+}
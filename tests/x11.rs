@@ -0,0 +1,210 @@
+//! Tests of the X11 backends against a virtual X server.
+//!
+//! They need `Xvfb` and `xclip` to be installed, so they are ignored by default.
+//! Run them with `cargo test --test x11 -- --ignored`.
+
+#![cfg(all(unix, not(target_os = "macos")))]
+
+use std::{
+  env,
+  io::Write,
+  path::Path,
+  process::{Child, Command, Stdio},
+  sync::{
+    Mutex, PoisonError,
+    atomic::{AtomicU32, Ordering},
+  },
+  thread,
+  time::{Duration, Instant},
+};
+
+use clipboard_watcher::{
+  Backend, Body, ClipboardEvent, ClipboardEventListener, ClipboardEventListenerBuilder,
+  ClipboardStream,
+};
+use futures::StreamExt;
+
+// The display is read from the environment, which is shared by the tests that run at the same time
+static ENVIRONMENT: Mutex<()> = Mutex::new(());
+static NEXT_DISPLAY: AtomicU32 = AtomicU32::new(90);
+
+/// A virtual X server, which is stopped when dropped.
+struct Xvfb {
+  process: Child,
+  display: String,
+}
+
+impl Xvfb {
+  fn start(args: &[&str]) -> Self {
+    // Skips the displays used by the servers that are already running
+    let number = loop {
+      let number = NEXT_DISPLAY.fetch_add(1, Ordering::Relaxed);
+
+      if !Path::new(&format!("/tmp/.X{number}-lock")).exists() {
+        break number;
+      }
+    };
+
+    let process = Command::new("Xvfb")
+      .arg(format!(":{number}"))
+      .args(["-nolisten", "tcp"])
+      .args(args)
+      .stdout(Stdio::null())
+      .stderr(Stdio::null())
+      .spawn()
+      .expect("Xvfb must be installed");
+
+    let xvfb = Xvfb {
+      process,
+      display: format!(":{number}"),
+    };
+
+    let socket = format!("/tmp/.X11-unix/X{number}");
+    let start = Instant::now();
+
+    while !Path::new(&socket).exists() {
+      assert!(
+        start.elapsed() < Duration::from_secs(10),
+        "Xvfb did not start"
+      );
+      thread::sleep(Duration::from_millis(10));
+    }
+
+    xvfb
+  }
+
+  /// Spawns a listener connected to this server.
+  fn spawn(&self, builder: ClipboardEventListenerBuilder) -> ClipboardEventListener {
+    let _guard = ENVIRONMENT.lock().unwrap_or_else(PoisonError::into_inner);
+
+    // SAFETY: the environment is only changed while holding the lock
+    unsafe {
+      env::set_var("DISPLAY", &self.display);
+      // Otherwise the Wayland backend would be picked
+      env::remove_var("WAYLAND_DISPLAY");
+    }
+
+    builder.interval(Duration::from_millis(20)).spawn().unwrap()
+  }
+
+  /// Copies text with `xclip`, which sends large content incrementally.
+  fn xclip(&self, text: &str) {
+    let mut xclip = Command::new("xclip")
+      .args(["-selection", "clipboard", "-i"])
+      .env("DISPLAY", &self.display)
+      .stdin(Stdio::piped())
+      .spawn()
+      .expect("xclip must be installed");
+
+    xclip
+      .stdin
+      .take()
+      .unwrap()
+      .write_all(text.as_bytes())
+      .unwrap();
+
+    // It keeps serving the content in the background until the server is stopped
+    assert!(xclip.wait().unwrap().success());
+  }
+}
+
+impl Drop for Xvfb {
+  fn drop(&mut self) {
+    let _ = self.process.kill();
+    let _ = self.process.wait();
+  }
+}
+
+fn text(text: &str) -> Body {
+  Body::PlainText(text.to_string())
+}
+
+async fn next(stream: &mut ClipboardStream) -> ClipboardEvent {
+  tokio::time::timeout(Duration::from_secs(10), stream.next())
+    .await
+    .expect("no change was reported")
+    .unwrap()
+    .unwrap()
+}
+
+#[tokio::test]
+#[ignore = "needs Xvfb"]
+async fn changes_are_reported_with_xfixes() {
+  let xvfb = Xvfb::start(&[]);
+
+  let mut listener = xvfb.spawn(ClipboardEventListener::builder());
+  assert_eq!(listener.backend(), Backend::X11XFixes);
+  let mut stream = listener.new_stream(32);
+
+  let writer = xvfb.spawn(ClipboardEventListener::builder());
+  writer.set(text("copied")).await.unwrap();
+
+  let event = next(&mut stream).await;
+  assert_eq!(event.body(), Some(&text("copied")));
+  assert!(!event.own_write);
+}
+
+#[tokio::test]
+#[ignore = "needs Xvfb"]
+async fn changes_are_polled_without_xfixes() {
+  let xvfb = Xvfb::start(&["-extension", "XFIXES"]);
+
+  let mut listener = xvfb.spawn(ClipboardEventListener::builder());
+  assert_eq!(listener.backend(), Backend::X11Polling);
+  let mut stream = listener.new_stream(32);
+
+  let writer = xvfb.spawn(ClipboardEventListener::builder());
+  writer.set(text("first")).await.unwrap();
+  assert_eq!(next(&mut stream).await.body(), Some(&text("first")));
+
+  writer.set(text("second")).await.unwrap();
+  assert_eq!(next(&mut stream).await.body(), Some(&text("second")));
+}
+
+#[tokio::test]
+#[ignore = "needs Xvfb and xclip"]
+async fn large_content_is_read_incrementally() {
+  let xvfb = Xvfb::start(&[]);
+
+  let mut listener = xvfb.spawn(ClipboardEventListener::builder());
+  let mut stream = listener.new_stream(32);
+
+  // Larger than the chunks that xclip sends at once, so it is sent with the INCR protocol
+  let large = "0123456789abcdef".repeat(256 * 1024);
+  xvfb.xclip(&large);
+
+  assert_eq!(next(&mut stream).await.body(), Some(&text(&large)));
+}
+
+#[tokio::test]
+#[ignore = "needs Xvfb"]
+async fn own_writes_are_flagged() {
+  let xvfb = Xvfb::start(&[]);
+
+  let mut listener = xvfb.spawn(ClipboardEventListener::builder());
+  let mut stream = listener.new_stream(32);
+
+  listener.set(text("mine")).await.unwrap();
+
+  let event = next(&mut stream).await;
+  assert_eq!(event.body(), Some(&text("mine")));
+  assert!(event.own_write);
+}
+
+#[tokio::test]
+#[ignore = "needs Xvfb"]
+async fn own_writes_can_be_ignored() {
+  let xvfb = Xvfb::start(&[]);
+
+  let mut listener = xvfb.spawn(ClipboardEventListener::builder().ignore_own_writes());
+  let mut stream = listener.new_stream(32);
+
+  listener.set(text("mine")).await.unwrap();
+
+  let writer = xvfb.spawn(ClipboardEventListener::builder());
+  writer.set(text("theirs")).await.unwrap();
+
+  let event = next(&mut stream).await;
+  assert_eq!(event.body(), Some(&text("theirs")));
+  assert!(!event.own_write);
+}